@@ -1,12 +1,19 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
 
 use clap::{Parser, Subcommand};
+use eyre::eyre;
 use figment::{
     Figment,
     providers::{Env, Format, Serialized, Toml},
 };
 use serde::{Deserialize, Serialize};
 
+use crate::{proxy_tags, sync_directive};
+
 #[derive(Parser, Debug, Deserialize, Serialize)]
 #[command(version)]
 pub(crate) struct CommandLine {
@@ -29,16 +36,42 @@ pub(crate) enum Command {
         /// Actually perform changes
         #[arg(short, long, default_value_t = false)]
         execute: bool,
+        /// Sync even if proxy tags of different members collide
+        #[arg(long, default_value_t = false)]
+        allow_proxy_collisions: bool,
+        /// Ask how to resolve fields edited both in the note and on pluralkit, needs `--execute`
+        #[arg(short, long, default_value_t = false)]
+        interactive: bool,
+    },
+    /// pull system from pluralkit into markdown
+    Pull {
+        /// Actually perform changes
+        #[arg(short, long, default_value_t = false)]
+        execute: bool,
+    },
+    /// create notes for all members and groups on pluralkit
+    Import {
+        /// Actually perform changes
+        #[arg(short, long, default_value_t = false)]
+        execute: bool,
     },
     /// list local groups and members
-    List,
+    List {
+        /// Show which frontmatter key each field was read from
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
     /// generate config file
     Config,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct NameConfig {
-    display_name_pronouns: bool,
+    /// Append the pronouns to the display name sent to pluralkit
+    pub(crate) display_name_pronouns: bool,
+    /// Display name sent to pluralkit, `{field}` is replaced with the mapped member field
+    #[serde(default)]
+    pub(crate) display_name_template: Option<String>,
 }
 
 #[expect(
@@ -49,7 +82,118 @@ impl Default for NameConfig {
     fn default() -> Self {
         Self {
             display_name_pronouns: false,
+            display_name_template: None,
+        }
+    }
+}
+
+/// What to do with fields that are longer than pluralkit allows
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LengthStrategy {
+    /// report an error and skip the file
+    #[default]
+    Error,
+    /// cut at the last word boundary that fits and append an ellipsis
+    TruncateWord,
+    /// cut at the last paragraph boundary that fits
+    TruncateParagraph,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct LimitConfig {
+    pub(crate) name: LengthStrategy,
+    pub(crate) display_name: LengthStrategy,
+    pub(crate) description: LengthStrategy,
+}
+
+/// How group memberships declared in markdown are applied to pluralkit
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MembershipMode {
+    /// only add missing memberships
+    #[default]
+    Add,
+    /// only remove memberships that aren't declared
+    Remove,
+    /// add missing and remove undeclared memberships
+    Overwrite,
+}
+
+impl MembershipMode {
+    pub(crate) fn adds(self) -> bool {
+        matches!(self, Self::Add | Self::Overwrite)
+    }
+
+    pub(crate) fn removes(self) -> bool {
+        matches!(self, Self::Remove | Self::Overwrite)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct MembershipConfig {
+    pub(crate) mode: MembershipMode,
+}
+
+/// Side that is authoritative for a field
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Owner {
+    /// only `sync` writes the field, `pull` leaves it alone
+    Markdown,
+    /// only `pull` writes the field, `sync` leaves it alone
+    PluralKit,
+}
+
+/// Owner of each field by sync plan name, fields without an owner are written in both directions
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub(crate) struct FieldOwners(BTreeMap<String, Owner>);
+
+impl FieldOwners {
+    /// Whether `sync` may write `field` to pluralkit
+    pub(crate) fn syncs(&self, field: &str) -> bool {
+        self.0.get(field) != Some(&Owner::PluralKit)
+    }
+
+    /// Whether one side is authoritative for `field`, owned fields aren't merged
+    pub(crate) fn is_owned(&self, field: &str) -> bool {
+        self.0.contains_key(field)
+    }
+
+    /// Whether `pull` may write `field` to markdown
+    pub(crate) fn pulls(&self, field: &str) -> bool {
+        self.0.get(field) != Some(&Owner::Markdown)
+    }
+
+    fn validate(&self, kind: &str, known: &[&str]) -> eyre::Result<()> {
+        for field in self.0.keys() {
+            if !known.contains(&field.as_str()) {
+                return Err(eyre!(
+                    "unknown {kind} field `{field}` in `ownership.{kind}`, valid fields are {}",
+                    known.join(", ")
+                ));
+            }
         }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct OwnershipConfig {
+    pub(crate) member: FieldOwners,
+    pub(crate) group: FieldOwners,
+}
+
+impl OwnershipConfig {
+    pub(crate) fn validate(&self) -> eyre::Result<()> {
+        self.member
+            .validate("member", sync_directive::MEMBER_FIELDS)?;
+        self.group.validate("group", sync_directive::GROUP_FIELDS)
     }
 }
 
@@ -61,6 +205,20 @@ pub(crate) struct Config {
     pub(crate) tag_scanner: TagScanConfig,
     pub(crate) token: String,
     pub(crate) name: NameConfig,
+    #[serde(default)]
+    pub(crate) limits: LimitConfig,
+    #[serde(default)]
+    pub(crate) membership: MembershipConfig,
+    #[serde(default)]
+    pub(crate) ownership: OwnershipConfig,
+    /// state of the last sync, the merge base telling note edits apart from PluralKit edits,
+    /// relative to the config file
+    #[serde(default = "default_snapshot_file")]
+    pub(crate) snapshot_file: String,
+}
+
+fn default_snapshot_file() -> String {
+    String::from("md2pk-snapshot.json")
 }
 
 impl Default for Config {
@@ -72,6 +230,10 @@ impl Default for Config {
             tag_scanner: TagScanConfig::default(),
             path_scanner: PathScanConfig::default(),
             name: NameConfig::default(),
+            limits: LimitConfig::default(),
+            membership: MembershipConfig::default(),
+            ownership: OwnershipConfig::default(),
+            snapshot_file: default_snapshot_file(),
         }
     }
 }
@@ -82,48 +244,184 @@ pub(crate) struct FieldConfig {
     pub(crate) group: GroupFieldConfig,
 }
 
+/// Where the pluralkit description is read from
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DescriptionSource {
+    /// don't sync descriptions
+    #[default]
+    None,
+    /// whole markdown body
+    Body,
+    /// section below `description_heading`
+    Heading,
+    /// frontmatter field `description`
+    Field,
+}
+
+fn default_proxy_tag_placeholder() -> String {
+    String::from(proxy_tags::DEFAULT_PLACEHOLDER)
+}
+
+fn default_sync_field() -> Option<FieldKeys> {
+    Some(FieldKeys::from("pk_sync"))
+}
+
+/// Frontmatter keys a field is read from, a single key or a list of keys tried in order
+///
+/// Keys the note doesn't have are read as paths into nested maps and lists, either dotted
+/// (`pluralkit.proxy.0`) or JSON pointers (`/pluralkit/proxy/0`).
+///
+/// Values are written back to the first key unless the note already uses another one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(crate) enum FieldKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl FieldKeys {
+    pub(crate) fn keys(&self) -> &[String] {
+        match self {
+            Self::One(key) => std::slice::from_ref(key),
+            Self::Many(keys) => keys,
+        }
+    }
+
+    /// Key new values are written to
+    pub(crate) fn primary(&self) -> &str {
+        self.keys().first().map_or("", String::as_str)
+    }
+}
+
+impl From<&str> for FieldKeys {
+    fn from(key: &str) -> Self {
+        Self::One(String::from(key))
+    }
+}
+
+impl fmt::Display for FieldKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.keys().join(" / "))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct MemberFieldConfig {
-    pub(crate) id: String,
-    pub(crate) uuid: Option<String>,
-    pub(crate) name: Option<String>,
-    pub(crate) display_name: Option<String>,
-    pub(crate) pronouns: Option<String>,
-    pub(crate) proxy_tags: Option<String>,
-    pub(crate) private: Option<String>,
+    /// Match frontmatter keys ignoring case when no key matches exactly
+    #[serde(default)]
+    pub(crate) case_insensitive: bool,
+    pub(crate) id: FieldKeys,
+    pub(crate) uuid: Option<FieldKeys>,
+    pub(crate) name: Option<FieldKeys>,
+    pub(crate) display_name: Option<FieldKeys>,
+    pub(crate) pronouns: Option<FieldKeys>,
+    pub(crate) proxy_tags: Option<FieldKeys>,
+    /// stands in for the message in proxy tags, `A:text` has the prefix `A:`
+    #[serde(default = "default_proxy_tag_placeholder")]
+    pub(crate) proxy_tag_placeholder: String,
+    pub(crate) private: Option<FieldKeys>,
+    /// a bool for every privacy setting, or a map of single settings to `public`/`private`
+    pub(crate) privacy: Option<FieldKeys>,
+    pub(crate) description: Option<FieldKeys>,
+    #[serde(default)]
+    pub(crate) description_source: DescriptionSource,
+    pub(crate) description_heading: Option<String>,
+    pub(crate) avatar_url: Option<FieldKeys>,
+    pub(crate) webhook_avatar_url: Option<FieldKeys>,
+    pub(crate) banner: Option<FieldKeys>,
+    pub(crate) color: Option<FieldKeys>,
+    pub(crate) birthday: Option<FieldKeys>,
+    pub(crate) keep_proxy: Option<FieldKeys>,
+    pub(crate) tts: Option<FieldKeys>,
+    pub(crate) autoproxy_enabled: Option<FieldKeys>,
+    /// group ids, group note names or wikilinks to group notes
+    pub(crate) groups: Option<FieldKeys>,
+    /// alternative names the note can be referenced by
+    pub(crate) aliases: Option<FieldKeys>,
+    /// `false` to leave the note out of sync, or a list of the fields to sync
+    #[serde(default = "default_sync_field")]
+    pub(crate) sync: Option<FieldKeys>,
 }
 
 impl Default for MemberFieldConfig {
     fn default() -> Self {
         MemberFieldConfig {
-            id: String::from("id"),
+            case_insensitive: false,
+            id: FieldKeys::from("id"),
             uuid: None,
-            name: Some(String::from("name")),
-            display_name: Some(String::from("display_name")),
-            pronouns: Some(String::from("pronouns")),
-            proxy_tags: Some(String::from("proxy_tags")),
-            private: Some(String::from("private")),
+            name: Some(FieldKeys::from("name")),
+            display_name: Some(FieldKeys::from("display_name")),
+            pronouns: Some(FieldKeys::from("pronouns")),
+            proxy_tags: Some(FieldKeys::from("proxy_tags")),
+            proxy_tag_placeholder: default_proxy_tag_placeholder(),
+            private: Some(FieldKeys::from("private")),
+            privacy: Some(FieldKeys::from("privacy")),
+            description: Some(FieldKeys::from("description")),
+            description_source: DescriptionSource::default(),
+            description_heading: None,
+            avatar_url: Some(FieldKeys::from("avatar_url")),
+            webhook_avatar_url: Some(FieldKeys::from("webhook_avatar_url")),
+            banner: Some(FieldKeys::from("banner")),
+            color: Some(FieldKeys::from("color")),
+            birthday: Some(FieldKeys::from("birthday")),
+            keep_proxy: Some(FieldKeys::from("keep_proxy")),
+            tts: Some(FieldKeys::from("tts")),
+            autoproxy_enabled: Some(FieldKeys::from("autoproxy_enabled")),
+            groups: Some(FieldKeys::from("groups")),
+            aliases: Some(FieldKeys::from("aliases")),
+            sync: default_sync_field(),
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(crate) struct GroupFieldConfig {
-    pub(crate) id: String,
-    pub(crate) uuid: Option<String>,
-    pub(crate) name: Option<String>,
-    pub(crate) display_name: Option<String>,
-    pub(crate) private: Option<String>,
+    /// Match frontmatter keys ignoring case when no key matches exactly
+    #[serde(default)]
+    pub(crate) case_insensitive: bool,
+    pub(crate) id: FieldKeys,
+    pub(crate) uuid: Option<FieldKeys>,
+    pub(crate) name: Option<FieldKeys>,
+    pub(crate) display_name: Option<FieldKeys>,
+    pub(crate) private: Option<FieldKeys>,
+    /// a bool for every privacy setting, or a map of single settings to `public`/`private`
+    pub(crate) privacy: Option<FieldKeys>,
+    pub(crate) description: Option<FieldKeys>,
+    #[serde(default)]
+    pub(crate) description_source: DescriptionSource,
+    pub(crate) description_heading: Option<String>,
+    pub(crate) icon: Option<FieldKeys>,
+    pub(crate) banner: Option<FieldKeys>,
+    pub(crate) color: Option<FieldKeys>,
+    /// member ids, member note names, aliases or wikilinks to member notes
+    pub(crate) members: Option<FieldKeys>,
+    /// heading of a bulleted list of `[[links]]` to member notes, used if `members` isn't set
+    pub(crate) members_heading: Option<String>,
+    /// `false` to leave the note out of sync, or a list of the fields to sync
+    #[serde(default = "default_sync_field")]
+    pub(crate) sync: Option<FieldKeys>,
 }
 
 impl Default for GroupFieldConfig {
     fn default() -> Self {
         GroupFieldConfig {
-            id: String::from("id"),
+            case_insensitive: false,
+            id: FieldKeys::from("id"),
             uuid: None,
-            name: Some(String::from("name")),
-            display_name: Some(String::from("display_name")),
-            private: Some(String::from("private")),
+            name: Some(FieldKeys::from("name")),
+            display_name: Some(FieldKeys::from("display_name")),
+            private: Some(FieldKeys::from("private")),
+            privacy: Some(FieldKeys::from("privacy")),
+            description: Some(FieldKeys::from("description")),
+            description_source: DescriptionSource::default(),
+            description_heading: None,
+            icon: Some(FieldKeys::from("icon")),
+            banner: Some(FieldKeys::from("banner")),
+            color: Some(FieldKeys::from("color")),
+            members: Some(FieldKeys::from("members")),
+            members_heading: None,
+            sync: default_sync_field(),
         }
     }
 }
@@ -133,6 +431,9 @@ pub(crate) struct TagScanConfig {
     pub(crate) root_dir: String,
     pub(crate) member_tags: Option<HashSet<String>>,
     pub(crate) group_tags: Option<HashSet<String>>,
+    /// Member notes tagged `<prefix>/<name>` belong to the group `name`
+    #[serde(default)]
+    pub(crate) group_tag_prefix: Option<String>,
 }
 
 impl Default for TagScanConfig {
@@ -147,6 +448,7 @@ impl Default for TagScanConfig {
                 String::from("#plurality"),
                 String::from("#system/group"),
             ])),
+            group_tag_prefix: None,
         }
     }
 }
@@ -156,6 +458,9 @@ pub(crate) struct PathScanConfig {
     pub(crate) recursive: bool,
     pub(crate) member_dir: Option<String>,
     pub(crate) group_dir: Option<String>,
+    /// Treat every subfolder of `member_dir` as a group its member notes belong to
+    #[serde(default)]
+    pub(crate) folder_groups: bool,
 }
 
 impl Default for PathScanConfig {
@@ -164,6 +469,7 @@ impl Default for PathScanConfig {
             recursive: false,
             member_dir: Some(String::from("~/notes/system/members")),
             group_dir: Some(String::from("~/notes/system/groups")),
+            folder_groups: false,
         }
     }
 }
@@ -171,10 +477,18 @@ impl Default for PathScanConfig {
 impl Config {
     #[expect(clippy::result_large_err, reason = "only used once")]
     pub(crate) fn load(flags: &CommandLine) -> eyre::Result<Config, figment::Error> {
-        Figment::new()
+        let mut conf: Config = Figment::new()
             .merge(Serialized::defaults(&flags))
             .merge(Toml::file(&flags.config))
             .merge(Env::prefixed("MD2PK_"))
-            .extract()
+            .extract()?;
+
+        // the snapshot belongs to the config, not to wherever sync happens to be run from
+        let snapshot = PathBuf::from(shellexpand::tilde(&conf.snapshot_file).as_ref());
+        if let Some(config_dir) = Path::new(&flags.config).parent() {
+            conf.snapshot_file = config_dir.join(snapshot).to_string_lossy().into_owned();
+        }
+
+        Ok(conf)
     }
 }
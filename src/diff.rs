@@ -0,0 +1,666 @@
+use eyre::eyre;
+use frontmatter_gen::Value;
+use pkrs_fork::model::{Group, Member, Privacy, ProxyTag};
+use time::Date;
+
+use crate::{
+    config::FieldOwners,
+    fields,
+    markdown_objects::{self, MarkdownGroup, MarkdownMember},
+    proxy_tags::{self, MarkdownProxyTag},
+    snapshot::FieldSnapshot,
+    sync_directive::SyncDirective,
+};
+
+/// Value of a single synced field, formatted for the sync plan
+///
+/// Proxy tags are written around `placeholder`, every other value ignores it.
+pub(crate) trait FieldValue: PartialEq + Clone + Sized {
+    fn display(&self, placeholder: &str) -> String;
+
+    /// Read a value typed in while resolving a conflict
+    fn parse(text: &str, placeholder: &str) -> eyre::Result<Self>;
+}
+
+impl FieldValue for String {
+    fn display(&self, _placeholder: &str) -> String {
+        format!("{self:?}")
+    }
+
+    /// Optional strings are cleared by [`Option`], a plain string always needs a value
+    fn parse(text: &str, _placeholder: &str) -> eyre::Result<Self> {
+        match text {
+            "" | "(none)" => Err(eyre!("a value is required")),
+            text => Ok(String::from(text)),
+        }
+    }
+}
+
+impl FieldValue for bool {
+    fn display(&self, _placeholder: &str) -> String {
+        self.to_string()
+    }
+
+    fn parse(text: &str, _placeholder: &str) -> eyre::Result<Self> {
+        fields::to_bool(&Value::String(String::from(text)))
+            .ok_or_else(|| eyre!("expected yes or no, got `{text}`"))
+    }
+}
+
+impl FieldValue for Date {
+    fn display(&self, _placeholder: &str) -> String {
+        self.to_string()
+    }
+
+    fn parse(text: &str, _placeholder: &str) -> eyre::Result<Self> {
+        markdown_objects::parse_birthday(None, Some(text))
+            .ok()
+            .flatten()
+            .ok_or_else(|| eyre!("expected a date formatted as `YYYY-MM-DD` or `MM-DD`"))
+    }
+}
+
+impl FieldValue for Privacy {
+    fn display(&self, _placeholder: &str) -> String {
+        String::from(match self {
+            Privacy::Public => "public",
+            Privacy::Private => "private",
+        })
+    }
+
+    fn parse(text: &str, _placeholder: &str) -> eyre::Result<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "public" => Ok(Privacy::Public),
+            "private" => Ok(Privacy::Private),
+            _ => Err(eyre!("expected `public` or `private`, got `{text}`")),
+        }
+    }
+}
+
+impl FieldValue for Vec<ProxyTag> {
+    fn display(&self, placeholder: &str) -> String {
+        let tags: Vec<String> = self
+            .iter()
+            .map(|tag| MarkdownProxyTag::from(tag).format(placeholder))
+            .collect();
+        format!("{tags:?}")
+    }
+
+    /// Comma separated tags around the placeholder
+    fn parse(text: &str, placeholder: &str) -> eyre::Result<Self> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| MarkdownProxyTag::parse(tag, placeholder).map(|tag| ProxyTag::from(&tag)))
+            .collect()
+    }
+}
+
+impl<T: FieldValue> FieldValue for Option<T> {
+    fn display(&self, placeholder: &str) -> String {
+        self.as_ref().map_or_else(
+            || String::from("(none)"),
+            |value| value.display(placeholder),
+        )
+    }
+
+    /// Nothing or `(none)` clears the value
+    fn parse(text: &str, placeholder: &str) -> eyre::Result<Self> {
+        match text {
+            "" | "(none)" => Ok(None),
+            text => T::parse(text, placeholder).map(Some),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct FieldChange {
+    pub(crate) field: &'static str,
+    pub(crate) remote: String,
+    pub(crate) local: String,
+}
+
+/// Field edited both in the note and on PluralKit since the last sync
+#[derive(Debug)]
+pub(crate) struct Conflict {
+    pub(crate) field: &'static str,
+    pub(crate) base: String,
+    pub(crate) remote: String,
+    pub(crate) local: String,
+}
+
+/// Choice made for a conflict, see [`Conflict`]
+pub(crate) enum Resolution {
+    /// send the note's value to PluralKit
+    Local,
+    /// keep PluralKit's value and write it to the note
+    Remote,
+    /// send a new value to PluralKit and write it to the note
+    Edit(String),
+    /// leave both sides alone
+    Skip,
+}
+
+/// Changes of a single note, fields the note's sync directive excludes or pluralkit owns are
+/// never recorded
+///
+/// With a snapshot of the last sync as merge base only fields edited in the note are changes,
+/// fields only edited on PluralKit are kept and fields edited on both sides are conflicts.
+struct Changes<'a> {
+    sync: &'a SyncDirective,
+    owners: &'a FieldOwners,
+    placeholder: &'a str,
+    base: Option<&'a FieldSnapshot>,
+    list: Vec<FieldChange>,
+    remote: Vec<FieldChange>,
+    conflicts: Vec<Conflict>,
+    edited: Vec<&'static str>,
+    snapshot: FieldSnapshot,
+    resolve: &'a mut dyn FnMut(&Conflict) -> Resolution,
+}
+
+impl<'a> Changes<'a> {
+    fn new(
+        sync: &'a SyncDirective,
+        owners: &'a FieldOwners,
+        placeholder: &'a str,
+        base: Option<&'a FieldSnapshot>,
+        resolve: &'a mut dyn FnMut(&Conflict) -> Resolution,
+    ) -> Self {
+        Self {
+            sync,
+            owners,
+            placeholder,
+            base,
+            list: Vec::new(),
+            remote: Vec::new(),
+            conflicts: Vec::new(),
+            edited: Vec::new(),
+            snapshot: FieldSnapshot::new(),
+            resolve,
+        }
+    }
+
+    fn includes(&self, field: &str) -> bool {
+        self.sync.includes(field) && self.owners.syncs(field)
+    }
+}
+
+/// Compare `local` against `remote`, on mismatch record the change and overwrite `remote`
+///
+/// `None` means the field isn't set in markdown and leaves `remote` untouched
+fn diff_field<T: FieldValue>(
+    changes: &mut Changes,
+    field: &'static str,
+    local: Option<T>,
+    remote: &mut T,
+) {
+    if changes.includes(field) {
+        let merge = !changes.owners.is_owned(field);
+        record_change(changes, field, merge, local, remote);
+    }
+}
+
+fn record_change<T: FieldValue>(
+    changes: &mut Changes,
+    field: &'static str,
+    merge: bool,
+    local: Option<T>,
+    remote: &mut T,
+) {
+    let Some(local) = local else {
+        return;
+    };
+
+    let (local_text, remote_text) = (
+        local.display(changes.placeholder),
+        remote.display(changes.placeholder),
+    );
+    let base = changes
+        .base
+        .filter(|_| merge)
+        .and_then(|base| base.get(field))
+        .cloned();
+
+    match base {
+        _ if local == *remote => {
+            changes.snapshot.insert(String::from(field), remote_text);
+        }
+        // only edited on pluralkit, the base moves once the note is updated too
+        Some(base) if base == local_text => {
+            changes.remote.push(FieldChange {
+                field,
+                remote: remote_text,
+                local: local_text,
+            });
+            changes.snapshot.insert(String::from(field), base);
+        }
+        Some(base) if base != remote_text => {
+            let conflict = Conflict {
+                field,
+                base,
+                remote: remote_text,
+                local: local_text,
+            };
+            resolve_conflict(changes, conflict, local, remote);
+        }
+        _ => {
+            changes.list.push(FieldChange {
+                field,
+                remote: remote_text,
+                local: local_text.clone(),
+            });
+            changes.snapshot.insert(String::from(field), local_text);
+            *remote = local;
+        }
+    }
+}
+
+/// Apply the choice made for `conflict`, the snapshot keeps the note's value for choices that
+/// still have to be written to the note so the next sync doesn't send the old value back
+fn resolve_conflict<T: FieldValue>(
+    changes: &mut Changes,
+    conflict: Conflict,
+    local: T,
+    remote: &mut T,
+) {
+    let field = conflict.field;
+    let edited = loop {
+        match (changes.resolve)(&conflict) {
+            Resolution::Local => break local,
+            Resolution::Remote => {
+                changes
+                    .snapshot
+                    .insert(String::from(field), conflict.local.clone());
+                changes.remote.push(FieldChange {
+                    field,
+                    remote: conflict.remote,
+                    local: conflict.local,
+                });
+                return;
+            }
+            Resolution::Edit(text) => match T::parse(&text, changes.placeholder) {
+                Ok(value) => {
+                    changes.edited.push(field);
+                    break value;
+                }
+                Err(err) => println!("ERROR {err}"),
+            },
+            Resolution::Skip => {
+                changes
+                    .snapshot
+                    .insert(String::from(field), conflict.base.clone());
+                changes.conflicts.push(conflict);
+                return;
+            }
+        }
+    };
+
+    changes.snapshot.insert(String::from(field), conflict.local);
+    changes.list.push(FieldChange {
+        field,
+        remote: conflict.remote,
+        local: edited.display(changes.placeholder),
+    });
+    *remote = edited;
+}
+
+fn privacy_from_private(private: bool) -> Privacy {
+    if private {
+        Privacy::Private
+    } else {
+        Privacy::Public
+    }
+}
+
+/// Compare a single privacy setting, all of them are synced as the `privacy` field
+fn diff_privacy(
+    changes: &mut Changes,
+    field: &'static str,
+    private: Option<bool>,
+    remote: &mut Option<Privacy>,
+) {
+    if !changes.includes("privacy") {
+        return;
+    }
+
+    let merge = !changes.owners.is_owned("privacy");
+    record_change(
+        changes,
+        field,
+        merge,
+        private.map(|private| Some(privacy_from_private(private))),
+        remote,
+    );
+}
+
+#[derive(Debug)]
+pub(crate) struct MemberDiff<'a> {
+    pub(crate) local: &'a MarkdownMember,
+    /// remote member with all local changes applied
+    pub(crate) patched: Member,
+    pub(crate) changes: Vec<FieldChange>,
+    /// fields only edited on PluralKit since the last sync, `remote` is the new value
+    pub(crate) remote_changes: Vec<FieldChange>,
+    pub(crate) conflicts: Vec<Conflict>,
+    /// conflicting fields set to a value typed in, they still have to be written to the note
+    pub(crate) edited: Vec<&'static str>,
+    /// merge base for the next sync once this one is applied
+    pub(crate) snapshot: FieldSnapshot,
+}
+
+impl<'a> MemberDiff<'a> {
+    /// `placeholder` is the proxy tag placeholder values are shown and typed in with
+    pub(crate) fn new(
+        local: &'a MarkdownMember,
+        remote: &Member,
+        owners: &FieldOwners,
+        placeholder: &str,
+        base: Option<&FieldSnapshot>,
+        resolve: &mut dyn FnMut(&Conflict) -> Resolution,
+    ) -> Self {
+        let mut patched = remote.clone();
+        let mut changes = Changes::new(&local.sync, owners, placeholder, base, resolve);
+
+        diff_field(&mut changes, "name", local.name.clone(), &mut patched.name);
+        diff_field(
+            &mut changes,
+            "display_name",
+            local.display_name.clone().map(Some),
+            &mut patched.display_name,
+        );
+        diff_field(
+            &mut changes,
+            "description",
+            local.description.clone().map(Some),
+            &mut patched.description,
+        );
+        diff_field(
+            &mut changes,
+            "pronouns",
+            (!local.pronouns.is_empty()).then(|| Some(local.pronouns.join(", "))),
+            &mut patched.pronouns,
+        );
+        diff_field(
+            &mut changes,
+            "proxy_tags",
+            (!local.proxy_tags.is_empty())
+                .then(|| local.proxy_tags.iter().map(ProxyTag::from).collect()),
+            &mut patched.proxy_tags,
+        );
+        diff_field(
+            &mut changes,
+            "avatar_url",
+            local.avatar_url.clone().map(Some),
+            &mut patched.avatar_url,
+        );
+        diff_field(
+            &mut changes,
+            "webhook_avatar_url",
+            local.webhook_avatar_url.clone().map(Some),
+            &mut patched.webhook_avatar_url,
+        );
+        diff_field(
+            &mut changes,
+            "banner",
+            local.banner.clone().map(Some),
+            &mut patched.banner,
+        );
+        diff_field(
+            &mut changes,
+            "color",
+            local.color.clone().map(Some),
+            &mut patched.color,
+        );
+        diff_field(
+            &mut changes,
+            "birthday",
+            local.birthday.map(Some),
+            &mut patched.birthday,
+        );
+        diff_field(
+            &mut changes,
+            "keep_proxy",
+            local.keep_proxy,
+            &mut patched.keep_proxy,
+        );
+        diff_field(&mut changes, "tts", local.tts, &mut patched.tts);
+        diff_field(
+            &mut changes,
+            "autoproxy_enabled",
+            local.autoproxy_enabled.map(Some),
+            &mut patched.autoproxy_enabled,
+        );
+
+        if !local.privacy.is_empty() {
+            let privacy = patched.privacy.get_or_insert_default();
+            for (field, key, remote) in [
+                ("visibility", "visibility", &mut privacy.visibility),
+                ("name_privacy", "name", &mut privacy.name_privacy),
+                (
+                    "description_privacy",
+                    "description",
+                    &mut privacy.description_privacy,
+                ),
+                (
+                    "birthday_privacy",
+                    "birthday",
+                    &mut privacy.birthday_privacy,
+                ),
+                ("pronoun_privacy", "pronouns", &mut privacy.pronoun_privacy),
+                ("avatar_privacy", "avatar", &mut privacy.avatar_privacy),
+                (
+                    "metadata_privacy",
+                    "metadata",
+                    &mut privacy.metadata_privacy,
+                ),
+                ("proxy_privacy", "proxy", &mut privacy.proxy_privacy),
+            ] {
+                diff_privacy(&mut changes, field, local.privacy.get(key), remote);
+            }
+        }
+
+        Self {
+            local,
+            patched,
+            changes: changes.list,
+            remote_changes: changes.remote,
+            conflicts: changes.conflicts,
+            edited: changes.edited,
+            snapshot: changes.snapshot,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct GroupDiff<'a> {
+    pub(crate) local: &'a MarkdownGroup,
+    /// remote group with all local changes applied
+    pub(crate) patched: Group,
+    pub(crate) changes: Vec<FieldChange>,
+    /// fields only edited on PluralKit since the last sync, `remote` is the new value
+    pub(crate) remote_changes: Vec<FieldChange>,
+    pub(crate) conflicts: Vec<Conflict>,
+    /// conflicting fields set to a value typed in, they still have to be written to the note
+    pub(crate) edited: Vec<&'static str>,
+    /// merge base for the next sync once this one is applied
+    pub(crate) snapshot: FieldSnapshot,
+}
+
+impl<'a> GroupDiff<'a> {
+    pub(crate) fn new(
+        local: &'a MarkdownGroup,
+        remote: &Group,
+        owners: &FieldOwners,
+        base: Option<&FieldSnapshot>,
+        resolve: &mut dyn FnMut(&Conflict) -> Resolution,
+    ) -> Self {
+        let mut patched = remote.clone();
+        // groups don't have proxy tags
+        let mut changes = Changes::new(
+            &local.sync,
+            owners,
+            proxy_tags::DEFAULT_PLACEHOLDER,
+            base,
+            resolve,
+        );
+
+        diff_field(&mut changes, "name", local.name.clone(), &mut patched.name);
+        diff_field(
+            &mut changes,
+            "display_name",
+            local.display_name.clone().map(Some),
+            &mut patched.display_name,
+        );
+        diff_field(
+            &mut changes,
+            "description",
+            local.description.clone().map(Some),
+            &mut patched.description,
+        );
+        diff_field(
+            &mut changes,
+            "icon",
+            local.icon.clone().map(Some),
+            &mut patched.icon,
+        );
+        diff_field(
+            &mut changes,
+            "banner",
+            local.banner.clone().map(Some),
+            &mut patched.banner,
+        );
+        diff_field(
+            &mut changes,
+            "color",
+            local.color.clone().map(Some),
+            &mut patched.color,
+        );
+
+        if !local.privacy.is_empty() {
+            let privacy = patched.privacy.get_or_insert_default();
+            for (field, key, remote) in [
+                ("visibility", "visibility", &mut privacy.visibility),
+                ("name_privacy", "name", &mut privacy.name_privacy),
+                (
+                    "description_privacy",
+                    "description",
+                    &mut privacy.description_privacy,
+                ),
+                ("icon_privacy", "icon", &mut privacy.icon_privacy),
+                ("list_privacy", "list", &mut privacy.list_privacy),
+                (
+                    "metadata_privacy",
+                    "metadata",
+                    &mut privacy.metadata_privacy,
+                ),
+                ("banner_privacy", "banner", &mut privacy.banner_privacy),
+            ] {
+                diff_privacy(&mut changes, field, local.privacy.get(key), remote);
+            }
+        }
+
+        Self {
+            local,
+            patched,
+            changes: changes.list,
+            remote_changes: changes.remote,
+            conflicts: changes.conflicts,
+            edited: changes.edited,
+            snapshot: changes.snapshot,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What [`record_change`] made of a `name` field with `base` as the last synced value
+    struct Recorded {
+        changes: Vec<FieldChange>,
+        remote_changes: Vec<FieldChange>,
+        conflicts: Vec<Conflict>,
+        snapshot: Option<String>,
+        patched: String,
+    }
+
+    fn record(base: &str, local: &str, remote: &str) -> Recorded {
+        let sync = SyncDirective::default();
+        let owners = FieldOwners::default();
+        let base = FieldSnapshot::from([(
+            String::from("name"),
+            String::from(base).display(proxy_tags::DEFAULT_PLACEHOLDER),
+        )]);
+        let mut resolve = |_: &Conflict| Resolution::Skip;
+        let mut changes = Changes::new(
+            &sync,
+            &owners,
+            proxy_tags::DEFAULT_PLACEHOLDER,
+            Some(&base),
+            &mut resolve,
+        );
+
+        let mut patched = String::from(remote);
+        record_change(
+            &mut changes,
+            "name",
+            true,
+            Some(String::from(local)),
+            &mut patched,
+        );
+
+        Recorded {
+            snapshot: changes.snapshot.get("name").cloned(),
+            changes: changes.list,
+            remote_changes: changes.remote,
+            conflicts: changes.conflicts,
+            patched,
+        }
+    }
+
+    #[test]
+    fn unchanged_field_records_nothing() {
+        let recorded = record("Alex", "Alex", "Alex");
+        assert!(recorded.changes.is_empty());
+        assert!(recorded.remote_changes.is_empty());
+        assert!(recorded.conflicts.is_empty());
+        assert_eq!(recorded.snapshot.as_deref(), Some("\"Alex\""));
+    }
+
+    #[test]
+    fn edit_on_pluralkit_is_kept() {
+        let recorded = record("Alex", "Alex", "Alexis");
+        assert!(recorded.changes.is_empty());
+        assert_eq!(recorded.remote_changes.len(), 1);
+        assert_eq!(recorded.remote_changes[0].remote, "\"Alexis\"");
+        assert!(recorded.conflicts.is_empty());
+        // the base only moves once the note has the new value too
+        assert_eq!(recorded.snapshot.as_deref(), Some("\"Alex\""));
+        assert_eq!(recorded.patched, "Alexis");
+    }
+
+    #[test]
+    fn edit_in_note_is_synced() {
+        let recorded = record("Alex", "Alexis", "Alex");
+        assert_eq!(recorded.changes.len(), 1);
+        assert_eq!(recorded.changes[0].local, "\"Alexis\"");
+        assert!(recorded.remote_changes.is_empty());
+        assert!(recorded.conflicts.is_empty());
+        assert_eq!(recorded.snapshot.as_deref(), Some("\"Alexis\""));
+        assert_eq!(recorded.patched, "Alexis");
+    }
+
+    #[test]
+    fn edit_on_both_sides_is_a_conflict() {
+        let recorded = record("Alex", "Alexis", "Lex");
+        assert!(recorded.changes.is_empty());
+        assert!(recorded.remote_changes.is_empty());
+        assert_eq!(recorded.conflicts.len(), 1);
+        assert_eq!(recorded.conflicts[0].base, "\"Alex\"");
+        assert_eq!(recorded.conflicts[0].local, "\"Alexis\"");
+        assert_eq!(recorded.conflicts[0].remote, "\"Lex\"");
+        assert_eq!(recorded.snapshot.as_deref(), Some("\"Alex\""));
+        assert_eq!(recorded.patched, "Lex");
+    }
+}
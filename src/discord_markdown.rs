@@ -0,0 +1,246 @@
+/// Remove `%%comments%%`, an unterminated comment runs to the end of the text
+fn strip_comments(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    while let Some(start) = rest.find("%%") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("%%") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+
+        // comments on their own lines don't leave an empty line behind
+        if out.is_empty() || out.ends_with('\n') {
+            rest = rest.strip_prefix('\n').unwrap_or(rest);
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Text shown for a `[[wikilink]]`, the part between the brackets is passed in
+fn link_text(link: &str) -> String {
+    if let Some((_, alias)) = link.split_once('|') {
+        return String::from(alias.trim());
+    }
+
+    let (target, subpath) = link.split_once('#').unwrap_or((link, ""));
+    let subpath = subpath.trim_start_matches('^');
+    match (target.trim(), subpath.trim()) {
+        (target, "") => String::from(target),
+        ("", subpath) => String::from(subpath),
+        (target, subpath) => format!("{target} > {subpath}"),
+    }
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Convert the inline obsidian syntax of a single line, code spans are kept as is
+fn convert_inline(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let fence = &rest[..ticks];
+            let span_end = rest[ticks..]
+                .find(fence)
+                .map_or(rest.len(), |end| ticks + end + ticks);
+            out.push_str(&rest[..span_end]);
+            rest = &rest[span_end..];
+            continue;
+        }
+
+        if let Some(embed) = rest.strip_prefix("![[")
+            && let Some(end) = embed.find("]]")
+        {
+            rest = &embed[end + 2..];
+            continue;
+        }
+
+        if let Some(link) = rest.strip_prefix("[[")
+            && let Some(end) = link.find("]]")
+        {
+            out.push_str(&link_text(&link[..end]));
+            rest = &link[end + 2..];
+            continue;
+        }
+
+        if let Some(highlight) = rest.strip_prefix("==")
+            && let Some(end) = highlight.find("==")
+            && end > 0
+        {
+            out.push_str("**");
+            out.push_str(&highlight[..end]);
+            out.push_str("**");
+            rest = &highlight[end + 2..];
+            continue;
+        }
+
+        if c == '#' && out.chars().last().is_none_or(char::is_whitespace) {
+            let tag_len = rest[1..]
+                .find(|c: char| !is_tag_char(c))
+                .unwrap_or(rest.len() - 1);
+            let tag = &rest[1..1 + tag_len];
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                let trimmed = out.trim_end().len();
+                out.truncate(trimmed);
+                rest = &rest[1 + tag_len..];
+                continue;
+            }
+        }
+
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    out
+}
+
+/// Turn `> [!type] Title` into a bold quote line, `None` if `line` isn't a callout header
+fn convert_callout(line: &str) -> Option<String> {
+    let quote_len = line.len() - line.trim_start_matches(['>', ' ']).len();
+    if quote_len == 0 || !line[..quote_len].contains('>') {
+        return None;
+    }
+
+    let callout = line[quote_len..].strip_prefix("[!")?;
+    let (kind, title) = callout.split_once(']')?;
+    let title = title.trim_start_matches(['+', '-']).trim();
+
+    let title = if title.is_empty() {
+        let mut chars = kind.chars();
+        chars.next().map_or_else(String::new, |first| {
+            first.to_uppercase().chain(chars).collect()
+        })
+    } else {
+        convert_inline(title)
+    };
+
+    Some(format!("> **{title}**"))
+}
+
+/// Turn `## Heading` into a bold line, `None` if `line` isn't a heading
+fn convert_heading(line: &str) -> Option<String> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+
+    let title = line[level..].strip_prefix([' ', '\t'])?.trim();
+    let title = title.trim_end_matches('#').trim_end();
+    Some(format!("**{}**", convert_inline(title)))
+}
+
+/// Convert obsidian flavoured markdown into the markdown subset discord renders
+///
+/// Wikilinks become plain text, embeds, comments and tags are removed, callouts turn into quotes,
+/// highlights and headings are turned bold.
+pub(crate) fn from_obsidian(markdown: &str) -> String {
+    let markdown = strip_comments(markdown);
+
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            lines.push(String::from(line));
+            continue;
+        }
+
+        if in_fence {
+            lines.push(String::from(line));
+            continue;
+        }
+
+        let converted = convert_callout(line)
+            .or_else(|| convert_heading(line))
+            .unwrap_or_else(|| convert_inline(line));
+
+        // drop lines that only contained removed syntax
+        if converted.trim().is_empty() && !line.trim().is_empty() {
+            continue;
+        }
+        lines.push(converted.trim_end().to_owned());
+    }
+
+    let mut out = String::with_capacity(markdown.len());
+    let mut blank = 0;
+    for line in lines {
+        if line.is_empty() {
+            blank += 1;
+            if blank > 1 {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+
+        out.push_str(&line);
+        out.push('\n');
+    }
+
+    String::from(out.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wikilinks_become_text() {
+        assert_eq!(from_obsidian("see [[Alex|our host]]"), "see our host");
+        assert_eq!(from_obsidian("see [[Alex]]"), "see Alex");
+        assert_eq!(from_obsidian("see [[Alex#Likes]]"), "see Alex > Likes");
+    }
+
+    #[test]
+    fn embeds_are_removed() {
+        assert_eq!(from_obsidian("avatar ![[alex.png]] here"), "avatar  here");
+        assert_eq!(from_obsidian("text\n![[alex.png]]\nmore"), "text\nmore");
+    }
+
+    #[test]
+    fn callout_header_becomes_bold_quote() {
+        assert_eq!(
+            from_obsidian("> [!note] About [[Alex]]\n> text"),
+            "> **About Alex**\n> text"
+        );
+        assert_eq!(
+            from_obsidian("> [!warning]-\n> text"),
+            "> **Warning**\n> text"
+        );
+    }
+
+    #[test]
+    fn comments_are_removed() {
+        assert_eq!(from_obsidian("a %%hidden%% b"), "a  b");
+        assert_eq!(from_obsidian("a\n%%hidden\nlines%%\nb"), "a\nb");
+        assert_eq!(from_obsidian("a %%unterminated\nb"), "a");
+    }
+
+    #[test]
+    fn tags_are_removed_but_not_in_urls_or_code() {
+        assert_eq!(from_obsidian("likes cats #system/alex"), "likes cats");
+        assert_eq!(
+            from_obsidian("https://example.com/#anchor and #1"),
+            "https://example.com/#anchor and #1"
+        );
+        assert_eq!(
+            from_obsidian("`#tag` and `[[link]]`"),
+            "`#tag` and `[[link]]`"
+        );
+        assert_eq!(
+            from_obsidian("```\n#tag [[link]]\n```"),
+            "```\n#tag [[link]]\n```"
+        );
+    }
+
+    #[test]
+    fn headings_and_highlights_become_bold() {
+        assert_eq!(from_obsidian("## Likes\n==cats=="), "**Likes**\n**cats**");
+    }
+}
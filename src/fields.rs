@@ -0,0 +1,284 @@
+use std::{cell::RefCell, path::Path};
+
+use frontmatter_gen::{Frontmatter, Value};
+
+use crate::config::FieldKeys;
+
+/// Coerce a bool, accepting yes/no, on/off, true/false and 1/0 in any case
+pub(crate) fn to_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Boolean(value) => Some(*value),
+        Value::Number(number) if *number == 1.0 => Some(true),
+        Value::Number(number) if *number == 0.0 => Some(false),
+        Value::String(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "yes" | "on" | "true" | "1" => Some(true),
+            "no" | "off" | "false" | "0" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Coerce a string, numbers are written without a fractional part where they don't have one
+pub(crate) fn to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+            Some(format!("{number:.0}"))
+        }
+        Value::Number(number) => Some(number.to_string()),
+        // unquoted `[[wikilinks]]` are parsed as a list in a list by YAML
+        Value::Array(inner) => match inner.as_slice() {
+            [Value::Array(link)] => match link.as_slice() {
+                [Value::String(link)] => Some(format!("[[{link}]]")),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Split a single string into list items on commas, or on slashes surrounded by spaces
+///
+/// Slashes without spaces are kept, so `she/her` stays a single item.
+fn split_list(value: &str) -> Vec<String> {
+    let items: Vec<&str> = if value.contains(',') {
+        value.split(',').collect()
+    } else {
+        value.split(" / ").collect()
+    };
+
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Segments of a nested field path, JSON pointers start with `/` and everything else is dotted
+pub(crate) fn path_segments(key: &str) -> Vec<String> {
+    match key.strip_prefix('/') {
+        Some(pointer) => pointer
+            .split('/')
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+        None => key.split('.').map(String::from).collect(),
+    }
+}
+
+/// Whether `key` is a path into nested values rather than a single top level key
+pub(crate) fn is_path(key: &str) -> bool {
+    key.starts_with('/') || path_segments(key).len() > 1
+}
+
+/// First of `available` that is `key`, exact matches are preferred over case-insensitive ones
+pub(crate) fn match_key<'a>(
+    available: impl Iterator<Item = &'a String> + Clone,
+    key: &str,
+    case_insensitive: bool,
+) -> Option<&'a String> {
+    available
+        .clone()
+        .find(|available| *available == key)
+        .or_else(|| {
+            case_insensitive
+                .then(|| {
+                    available
+                        .into_iter()
+                        .find(|available| available.eq_ignore_ascii_case(key))
+                })
+                .flatten()
+        })
+}
+
+/// Reads the frontmatter fields of a single note, coercing values into the expected type
+///
+/// Values that can't be coerced are reported and treated as not set.
+pub(crate) struct FieldReader<'a> {
+    path: &'a Path,
+    frontmatter: &'a Frontmatter,
+    case_insensitive: bool,
+    /// configured primary key and the key a value was actually read from
+    used_keys: RefCell<Vec<(String, String)>>,
+}
+
+impl<'a> FieldReader<'a> {
+    pub(crate) fn new(
+        path: &'a Path,
+        frontmatter: &'a Frontmatter,
+        case_insensitive: bool,
+    ) -> Self {
+        Self {
+            path,
+            frontmatter,
+            case_insensitive,
+            used_keys: RefCell::default(),
+        }
+    }
+
+    pub(crate) fn into_used_keys(self) -> Vec<(String, String)> {
+        self.used_keys.into_inner()
+    }
+
+    fn warn(&self, field: &str, value: &Value, kind: &str) {
+        println!(
+            "WARN  {}: couldn't read `{field}` value `{value:?}` as {kind}, ignoring it",
+            self.path.display()
+        );
+    }
+
+    /// Value at `key`, a key the note doesn't have is read as a path, see [`path_segments`]
+    fn lookup(&self, key: &str) -> Option<(String, &'a Value)> {
+        let keys = self.frontmatter.iter().map(|(key, _)| key);
+        if let Some(found) = match_key(keys, key, self.case_insensitive) {
+            return Some((found.clone(), self.frontmatter.get(found)?));
+        }
+
+        if !is_path(key) {
+            return None;
+        }
+
+        let segments = path_segments(key);
+        let (first, rest) = segments.split_first()?;
+        let keys = self.frontmatter.iter().map(|(key, _)| key);
+        let mut value = self
+            .frontmatter
+            .get(match_key(keys, first, self.case_insensitive)?)?;
+        for segment in rest {
+            value = match value {
+                Value::Object(map) => {
+                    let keys = map.iter().map(|(key, _)| key);
+                    map.get(match_key(keys, segment, self.case_insensitive)?)?
+                }
+                Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+
+        Some((String::from(key), value))
+    }
+
+    /// Raw value of the first of `keys` the note has, null values count as not set
+    pub(crate) fn value(&self, keys: Option<&FieldKeys>) -> Option<(String, &'a Value)> {
+        let keys = keys?;
+        let (field, value) = keys.keys().iter().find_map(|key| self.lookup(key))?;
+        if value.is_null() {
+            return None;
+        }
+
+        self.used_keys
+            .borrow_mut()
+            .push((String::from(keys.primary()), field.clone()));
+        Some((field, value))
+    }
+
+    pub(crate) fn string(&self, field: Option<&FieldKeys>) -> Option<String> {
+        let (field, value) = self.value(field)?;
+        let coerced = to_string(value);
+        if coerced.is_none() {
+            self.warn(&field, value, "text");
+        }
+        coerced
+    }
+
+    pub(crate) fn bool(&self, field: Option<&FieldKeys>) -> Option<bool> {
+        let (field, value) = self.value(field)?;
+        let coerced = to_bool(value);
+        if coerced.is_none() {
+            self.warn(&field, value, "yes or no");
+        }
+        coerced
+    }
+
+    /// A list, or a single string split into one, see [`split_list`]
+    pub(crate) fn list(&self, field: Option<&FieldKeys>) -> Option<Vec<String>> {
+        let (field, value) = self.value(field)?;
+        self.coerce_list(&field, value)
+    }
+
+    /// Coerce an already read `value` of `field` into a list, see [`FieldReader::list`]
+    pub(crate) fn coerce_list(&self, field: &str, value: &Value) -> Option<Vec<String>> {
+        // a single unquoted `[[wikilink]]`
+        if let Value::Array(_) = value
+            && let Some(link) = to_string(value)
+        {
+            return Some(vec![link]);
+        }
+
+        match value {
+            Value::Array(items) => Some(
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        let coerced = to_string(item);
+                        if coerced.is_none() {
+                            self.warn(field, item, "text");
+                        }
+                        coerced
+                    })
+                    .collect(),
+            ),
+            Value::String(value) => Some(split_list(value)),
+            value => match to_string(value) {
+                Some(item) => Some(vec![item]),
+                None => {
+                    self.warn(field, value, "a list");
+                    None
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_bool_accepts_common_spellings() {
+        for yes in ["yes", "On", " TRUE ", "1"] {
+            assert_eq!(to_bool(&Value::String(String::from(yes))), Some(true));
+        }
+        for no in ["no", "OFF", "false", "0"] {
+            assert_eq!(to_bool(&Value::String(String::from(no))), Some(false));
+        }
+        assert_eq!(to_bool(&Value::Number(1.0)), Some(true));
+        assert_eq!(to_bool(&Value::Number(0.0)), Some(false));
+        assert_eq!(to_bool(&Value::Number(2.0)), None);
+        assert_eq!(to_bool(&Value::String(String::from("maybe"))), None);
+    }
+
+    #[test]
+    fn to_string_writes_whole_numbers_without_fraction() {
+        assert_eq!(to_string(&Value::Number(42.0)).as_deref(), Some("42"));
+        assert_eq!(to_string(&Value::Number(-3.0)).as_deref(), Some("-3"));
+        assert_eq!(to_string(&Value::Number(1.5)).as_deref(), Some("1.5"));
+        assert_eq!(to_string(&Value::Boolean(true)), None);
+    }
+
+    #[test]
+    fn to_string_reads_unquoted_wikilinks() {
+        let link = Value::Array(vec![Value::Array(vec![Value::String(String::from(
+            "Alex",
+        ))])]);
+        assert_eq!(to_string(&link).as_deref(), Some("[[Alex]]"));
+    }
+
+    #[test]
+    fn single_segment_pointers_are_paths() {
+        assert!(is_path("/id"));
+        assert!(is_path("pluralkit.id"));
+        assert!(!is_path("ID"));
+        assert_eq!(path_segments("/id"), ["id"]);
+    }
+
+    #[test]
+    fn split_list_keeps_pronouns_together() {
+        assert_eq!(split_list("she/her"), ["she/her"]);
+        assert_eq!(split_list("she/her, they/them"), ["she/her", "they/them"]);
+        assert_eq!(split_list("she / they"), ["she", "they"]);
+        assert_eq!(split_list("a,, b ,"), ["a", "b"]);
+    }
+}
@@ -0,0 +1,953 @@
+use std::{fmt, path::Path};
+
+use eyre::eyre;
+
+use crate::{config::FieldKeys, fields};
+
+/// Value that can be written to a frontmatter key
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum YamlValue {
+    Bool(bool),
+    String(String),
+    List(Vec<String>),
+}
+
+impl fmt::Display for YamlValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(val) => write!(f, "{val}"),
+            Self::String(val) => write!(f, "{val:?}"),
+            Self::List(items) => write!(f, "{items:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QuoteStyle {
+    Plain,
+    Single,
+    Double,
+}
+
+impl QuoteStyle {
+    fn of(raw: &str) -> Self {
+        if raw.starts_with('\'') {
+            Self::Single
+        } else if raw.starts_with('"') {
+            Self::Double
+        } else {
+            Self::Plain
+        }
+    }
+}
+
+/// Layout of an existing value, reused when it gets replaced
+#[derive(Debug)]
+enum Shape {
+    Scalar(QuoteStyle),
+    FlowList(QuoteStyle),
+    BlockList { indent: String, style: QuoteStyle },
+    Empty,
+}
+
+/// Lines of a block map, only keys indented by exactly `indent` belong to it
+#[derive(Debug, Clone, Copy)]
+struct Scope {
+    start: usize,
+    end: usize,
+    indent: usize,
+}
+
+/// Line based YAML frontmatter editor
+///
+/// Only the lines belonging to keys that are changed get rewritten, comments, quoting, key order
+/// and the markdown body are kept byte-identical.
+#[derive(Debug)]
+pub(crate) struct FrontmatterEditor {
+    newline: &'static str,
+    /// opening `---` line, empty if the note has no frontmatter
+    open: String,
+    /// frontmatter lines including their line endings
+    lines: Vec<String>,
+    /// closing `---` line followed by the markdown body
+    rest: String,
+}
+
+impl FrontmatterEditor {
+    pub(crate) fn parse(content: &str) -> eyre::Result<Self> {
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let mut lines = content.split_inclusive('\n');
+        let Some(open) = lines.next().filter(|line| trim_newline(line) == "---") else {
+            return Ok(Self {
+                newline,
+                open: String::new(),
+                lines: Vec::new(),
+                rest: String::from(content),
+            });
+        };
+
+        let mut offset = open.len();
+        let mut frontmatter = Vec::new();
+        for line in lines {
+            if matches!(trim_newline(line), "---" | "...") {
+                return Ok(Self {
+                    newline,
+                    open: String::from(open),
+                    lines: frontmatter,
+                    rest: String::from(&content[offset..]),
+                });
+            }
+
+            offset += line.len();
+            frontmatter.push(String::from(line));
+        }
+
+        Err(eyre!("unterminated frontmatter"))
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let mut content = self.open.clone();
+        for line in &self.lines {
+            content.push_str(line);
+        }
+        content.push_str(&self.rest);
+        content
+    }
+
+    fn top(&self) -> Scope {
+        Scope {
+            start: 0,
+            end: self.lines.len(),
+            indent: 0,
+        }
+    }
+
+    fn ensure_frontmatter(&mut self) {
+        if self.open.is_empty() {
+            self.open = format!("---{}", self.newline);
+            self.rest = format!("---{}{}", self.newline, self.rest);
+        }
+    }
+
+    /// Set the top level `key` to `value`, returns whether anything changed
+    pub(crate) fn set(&mut self, key: &str, value: &YamlValue) -> bool {
+        self.set_in(self.top(), key, value)
+    }
+
+    /// Set `key` to `value`, a key the note doesn't have is read as a nested path
+    ///
+    /// Paths can only be written into block maps and lists, see [`fields::path_segments`].
+    pub(crate) fn set_field(
+        &mut self,
+        key: &str,
+        value: &YamlValue,
+        case_insensitive: bool,
+    ) -> eyre::Result<bool> {
+        if !fields::is_path(key) || self.find_in(self.top(), key, false).is_some() {
+            return Ok(self.set(key, value));
+        }
+
+        let segments = fields::path_segments(key);
+        self.set_path(self.top(), &segments, value, case_insensitive)
+    }
+
+    /// Key a field is written to, the first of `keys` the note already has or else the primary key
+    pub(crate) fn resolve_key(&self, keys: &FieldKeys, case_insensitive: bool) -> String {
+        for key in keys.keys() {
+            if let Some((idx, value_start)) = self.find_in(self.top(), key, case_insensitive) {
+                return String::from(self.lines[idx][..value_start - 1].trim_matches(['"', '\'']));
+            }
+
+            if fields::is_path(key) && self.find_path(&fields::path_segments(key), case_insensitive)
+            {
+                return key.clone();
+            }
+        }
+
+        String::from(keys.primary())
+    }
+
+    fn set_in(&mut self, scope: Scope, key: &str, value: &YamlValue) -> bool {
+        let Some((idx, value_start)) = self.find_in(scope, key, false) else {
+            self.ensure_frontmatter();
+
+            let key_text = if needs_quotes(key, false) {
+                double_quote(key)
+            } else {
+                String::from(key)
+            };
+            let indent = " ".repeat(scope.indent);
+            let new_lines =
+                self.render_value(&format!("{indent}{key_text}:"), "", value, &Shape::Empty);
+            self.lines.splice(scope.end..scope.end, new_lines);
+            return true;
+        };
+
+        let end = self.value_end(idx, scope.indent);
+        let (key_prefix, inline) = self.lines[idx].split_at(value_start);
+        let (inline, comment) = split_comment(trim_newline(inline));
+        let (existing, shape) = parse_value(inline.trim(), &self.lines[idx + 1..end]);
+        if existing.as_ref() == Some(value) {
+            return false;
+        }
+
+        let new_lines = self.render_value(key_prefix, comment, value, &shape);
+        self.lines.splice(idx..end, new_lines);
+        true
+    }
+
+    fn set_path(
+        &mut self,
+        scope: Scope,
+        segments: &[String],
+        value: &YamlValue,
+        case_insensitive: bool,
+    ) -> eyre::Result<bool> {
+        let Some((first, rest)) = segments.split_first() else {
+            return Ok(false);
+        };
+        if rest.is_empty() {
+            let key = self.find_in(scope, first, case_insensitive).map_or_else(
+                || first.clone(),
+                |(idx, value_start)| {
+                    let key = &self.lines[idx][scope.indent..value_start - 1];
+                    String::from(key.trim_matches(['"', '\'']))
+                },
+            );
+            return Ok(self.set_in(scope, &key, value));
+        }
+
+        let Some((idx, value_start)) = self.find_in(scope, first, case_insensitive) else {
+            // create the missing map
+            self.ensure_frontmatter();
+            let indent = " ".repeat(scope.indent);
+            let key_text = if needs_quotes(first, false) {
+                double_quote(first)
+            } else {
+                first.clone()
+            };
+            self.lines
+                .insert(scope.end, format!("{indent}{key_text}:{}", self.newline));
+            let child = Scope {
+                start: scope.end + 1,
+                end: scope.end + 1,
+                indent: scope.indent + 2,
+            };
+            return self.set_path(child, rest, value, case_insensitive);
+        };
+
+        let (inline, _) = split_comment(trim_newline(&self.lines[idx][value_start..]));
+        if !inline.trim().is_empty() {
+            return Err(eyre!(
+                "can't write `{}` into `{first}`, it isn't a block map or list",
+                segments.join(".")
+            ));
+        }
+
+        let end = self.value_end(idx, scope.indent);
+        let Some((content_idx, content_indent)) = self.first_content(idx + 1, end) else {
+            let child = Scope {
+                start: idx + 1,
+                end,
+                indent: scope.indent + 2,
+            };
+            return self.set_path(child, rest, value, case_insensitive);
+        };
+
+        if self.lines[content_idx][content_indent..].starts_with('-') {
+            return self.set_list_item(idx + 1, end, content_indent, rest, value);
+        }
+
+        let child = Scope {
+            start: idx + 1,
+            end,
+            indent: content_indent,
+        };
+        self.set_path(child, rest, value, case_insensitive)
+    }
+
+    /// Set a scalar item of the block list on lines `start..end`, one past the last item appends
+    fn set_list_item(
+        &mut self,
+        start: usize,
+        end: usize,
+        indent: usize,
+        segments: &[String],
+        value: &YamlValue,
+    ) -> eyre::Result<bool> {
+        let Some(index) = segments
+            .first()
+            .and_then(|index| index.parse::<usize>().ok())
+        else {
+            return Err(eyre!("list entries can only be addressed by index"));
+        };
+        if segments.len() > 1 {
+            return Err(eyre!("only scalar list entries can be written"));
+        }
+
+        let text = match value {
+            YamlValue::Bool(val) => val.to_string(),
+            YamlValue::String(val) => val.clone(),
+            YamlValue::List(_) => return Err(eyre!("list entries can't be lists")),
+        };
+
+        let items: Vec<usize> = (start..end)
+            .filter(|idx| {
+                let line = &self.lines[*idx];
+                line.len() > indent
+                    && line[..indent].trim().is_empty()
+                    && line[indent..].starts_with('-')
+            })
+            .collect();
+        let prefix = format!("{}- ", " ".repeat(indent));
+
+        let Some(&item_idx) = items.get(index) else {
+            if index != items.len() {
+                return Err(eyre!("list index {index} is out of range"));
+            }
+            let style = items.last().map_or(QuoteStyle::Plain, |last| {
+                QuoteStyle::of(self.lines[*last][indent + 1..].trim_start())
+            });
+            let line = format!("{prefix}{}{}", quote(&text, style, false), self.newline);
+            let insert_at = items
+                .last()
+                .map_or(end, |last| self.value_end(*last, indent));
+            self.lines.insert(insert_at, line);
+            return Ok(true);
+        };
+
+        let raw = trim_newline(&self.lines[item_idx])[indent + 1..].trim_start();
+        let (raw, comment) = split_comment(raw);
+        if unquote(raw.trim()).as_deref() == Some(text.as_str()) {
+            return Ok(false);
+        }
+
+        let style = QuoteStyle::of(raw);
+        self.lines[item_idx] = format!(
+            "{prefix}{}{comment}{}",
+            quote(&text, style, false),
+            self.newline
+        );
+        Ok(true)
+    }
+
+    /// Whether the note has a value at `segments`
+    fn find_path(&self, segments: &[String], case_insensitive: bool) -> bool {
+        let mut scope = self.top();
+        let mut segments = segments.iter().peekable();
+        while let Some(segment) = segments.next() {
+            let Some((idx, _)) = self.find_in(scope, segment, case_insensitive) else {
+                return false;
+            };
+            if segments.peek().is_none() {
+                return true;
+            }
+
+            let end = self.value_end(idx, scope.indent);
+            let Some((content_idx, indent)) = self.first_content(idx + 1, end) else {
+                return false;
+            };
+            if self.lines[content_idx][indent..].starts_with('-') {
+                // list items are only ever the last segment
+                let index = segments
+                    .next()
+                    .and_then(|index| index.parse::<usize>().ok());
+                let count = (idx + 1..end)
+                    .filter(|line| {
+                        let line = &self.lines[*line];
+                        line.len() > indent
+                            && line[..indent].trim().is_empty()
+                            && line[indent..].starts_with('-')
+                    })
+                    .count();
+                return segments.peek().is_none() && index.is_some_and(|index| index < count);
+            }
+
+            scope = Scope {
+                start: idx + 1,
+                end,
+                indent,
+            };
+        }
+
+        false
+    }
+
+    /// First line in `start..end` that isn't blank or a comment, and its indent
+    fn first_content(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        (start..end).find_map(|idx| {
+            let line = trim_newline(&self.lines[idx]);
+            let trimmed = line.trim_start();
+            (!trimmed.is_empty() && !trimmed.starts_with('#'))
+                .then_some((idx, line.len() - trimmed.len()))
+        })
+    }
+
+    /// Line and value offset of `key` directly in `scope`, exact matches are preferred
+    fn find_in(&self, scope: Scope, key: &str, case_insensitive: bool) -> Option<(usize, usize)> {
+        let keys: Vec<(usize, String, usize)> = (scope.start..scope.end)
+            .filter_map(|idx| {
+                let line = &self.lines[idx];
+                let indent = line.len() - line.trim_start_matches(' ').len();
+                (indent == scope.indent)
+                    .then(|| parse_key(&line[indent..]))
+                    .flatten()
+                    .map(|(line_key, value_start)| (idx, line_key, indent + value_start))
+            })
+            .collect();
+
+        keys.iter()
+            .find(|(_, line_key, _)| line_key == key)
+            .or_else(|| {
+                case_insensitive
+                    .then(|| {
+                        keys.iter()
+                            .find(|(_, line_key, _)| line_key.eq_ignore_ascii_case(key))
+                    })
+                    .flatten()
+            })
+            .map(|(idx, _, value_start)| (*idx, *value_start))
+    }
+
+    /// Index after the last line belonging to the value of the key on line `idx`
+    fn value_end(&self, idx: usize, indent: usize) -> usize {
+        let mut end = idx + 1;
+        for (offset, line) in self.lines[idx + 1..].iter().enumerate() {
+            let line = trim_newline(line);
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let line_indent = line.len() - line.trim_start().len();
+            let list_item = trimmed == "-" || trimmed.starts_with("- ");
+            if line_indent > indent || (line_indent == indent && list_item) {
+                end = idx + 1 + offset + 1;
+            } else {
+                break;
+            }
+        }
+
+        end
+    }
+
+    fn render_value(
+        &self,
+        key_prefix: &str,
+        comment: &str,
+        value: &YamlValue,
+        shape: &Shape,
+    ) -> Vec<String> {
+        let nl = self.newline;
+        let key_indent = key_prefix.len() - key_prefix.trim_start().len();
+        let default_indent = " ".repeat(key_indent + 2);
+        match value {
+            YamlValue::Bool(val) => vec![format!("{key_prefix} {val}{comment}{nl}")],
+            YamlValue::String(val) => {
+                let style = match shape {
+                    Shape::Scalar(style) => *style,
+                    _ => QuoteStyle::Plain,
+                };
+                vec![format!(
+                    "{key_prefix} {}{comment}{nl}",
+                    quote(val, style, false)
+                )]
+            }
+            YamlValue::List(items) => match shape {
+                Shape::FlowList(style) => {
+                    let items: Vec<String> =
+                        items.iter().map(|item| quote(item, *style, true)).collect();
+                    vec![format!("{key_prefix} [{}]{comment}{nl}", items.join(", "))]
+                }
+                _ if items.is_empty() => vec![format!("{key_prefix} []{comment}{nl}")],
+                _ => {
+                    let (indent, style) = match shape {
+                        Shape::BlockList { indent, style } => (indent.as_str(), *style),
+                        _ => (default_indent.as_str(), QuoteStyle::Plain),
+                    };
+
+                    let mut lines = vec![format!("{key_prefix}{comment}{nl}")];
+                    for item in items {
+                        lines.push(format!("{indent}- {}{nl}", quote(item, style, false)));
+                    }
+                    lines
+                }
+            },
+        }
+    }
+}
+
+/// Read the note at `path`, apply `edit` to its frontmatter and write it back if anything changed
+pub(crate) fn edit_file(
+    path: &Path,
+    edit: impl FnOnce(&mut FrontmatterEditor) -> eyre::Result<()>,
+) -> eyre::Result<bool> {
+    let content = std::fs::read_to_string(path)?;
+    let mut editor = FrontmatterEditor::parse(&content)?;
+    edit(&mut editor)?;
+
+    let new_content = editor.render();
+    if new_content == content {
+        return Ok(false);
+    }
+
+    std::fs::write(path, new_content)?;
+    Ok(true)
+}
+
+fn trim_newline(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+/// Parse a top-level `key:` line, returning the unquoted key and the offset its value starts at
+fn parse_key(line: &str) -> Option<(String, usize)> {
+    let first = line.chars().next()?;
+    if first.is_whitespace() || first == '#' || first == '-' {
+        return None;
+    }
+
+    if first == '"' || first == '\'' {
+        let end = line[1..].find(first)? + 1;
+        line[end + 1..].starts_with(':').then(|| {
+            let key = &line[1..end];
+            (
+                unquote(&line[..=end]).unwrap_or_else(|| String::from(key)),
+                end + 2,
+            )
+        })
+    } else {
+        line.char_indices().find_map(|(idx, c)| {
+            (c == ':'
+                && line[idx + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace))
+            .then(|| (String::from(line[..idx].trim_end()), idx + 1))
+        })
+    }
+}
+
+/// Split a trailing ` # comment` off a value, the comment keeps its leading whitespace
+fn split_comment(value: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut prev_whitespace = true;
+    for (idx, c) in value.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '#') if prev_whitespace => {
+                let start = value[..idx].trim_end().len();
+                return value.split_at(start);
+            }
+            _ => {}
+        }
+        prev_whitespace = c.is_whitespace();
+    }
+
+    (value, "")
+}
+
+fn parse_value(inline: &str, continuation: &[String]) -> (Option<YamlValue>, Shape) {
+    if let Some(flow) = inline.strip_prefix('[') {
+        let flow = flow.strip_suffix(']').unwrap_or(flow);
+        let items = split_flow(flow);
+        let style = items
+            .first()
+            .map_or(QuoteStyle::Plain, |item| QuoteStyle::of(item));
+        let items: Option<Vec<String>> = items.iter().map(|item| unquote(item)).collect();
+        return (items.map(YamlValue::List), Shape::FlowList(style));
+    }
+
+    if !inline.is_empty() {
+        let value = match inline {
+            "true" => Some(YamlValue::Bool(true)),
+            "false" => Some(YamlValue::Bool(false)),
+            _ => unquote(inline).map(YamlValue::String),
+        };
+        return (value, Shape::Scalar(QuoteStyle::of(inline)));
+    }
+
+    let items: Vec<&str> = continuation
+        .iter()
+        .map(|line| trim_newline(line))
+        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .collect();
+
+    let Some(first) = items.first() else {
+        return (None, Shape::Empty);
+    };
+
+    let trimmed = first.trim_start();
+    if trimmed != "-" && !trimmed.starts_with("- ") {
+        return (None, Shape::Empty);
+    }
+
+    let indent = String::from(&first[..first.len() - trimmed.len()]);
+    let style = QuoteStyle::of(trimmed.trim_start_matches('-').trim_start());
+    let values: Option<Vec<String>> = items
+        .iter()
+        .map(|item| {
+            item.trim_start()
+                .strip_prefix('-')
+                .and_then(|item| unquote(split_comment(item).0.trim()))
+        })
+        .collect();
+
+    (
+        values.map(YamlValue::List),
+        Shape::BlockList { indent, style },
+    )
+}
+
+fn split_flow(flow: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (idx, c) in flow.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, ',') => {
+                items.push(flow[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(flow[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Unquote a scalar, `None` for values this editor doesn't understand
+fn unquote(raw: &str) -> Option<String> {
+    match QuoteStyle::of(raw) {
+        QuoteStyle::Single => raw
+            .strip_prefix('\'')?
+            .strip_suffix('\'')
+            .map(|inner| inner.replace("''", "'")),
+        QuoteStyle::Double => serde_json::from_str(raw).ok(),
+        QuoteStyle::Plain => {
+            (!raw.starts_with(['|', '>', '{', '[', '&', '*', '!'])).then(|| String::from(raw))
+        }
+    }
+}
+
+fn needs_quotes(value: &str, flow: bool) -> bool {
+    value.is_empty()
+        || value != value.trim()
+        || value.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.ends_with(':')
+        || value.contains(['\n', '\r', '\t'])
+        || (flow && value.contains([',', '[', ']', '{', '}']))
+        || matches!(
+            value.to_ascii_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n" | "null" | "~"
+        )
+        || value.parse::<f64>().is_ok()
+}
+
+fn double_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn quote(value: &str, style: QuoteStyle, flow: bool) -> String {
+    match style {
+        QuoteStyle::Plain if !needs_quotes(value, flow) => String::from(value),
+        QuoteStyle::Single if !value.contains(['\n', '\r', '\t']) => {
+            format!("'{}'", value.replace('\'', "''"))
+        }
+        _ => double_quote(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(content: &str, edit: impl FnOnce(&mut FrontmatterEditor)) -> String {
+        let mut editor = FrontmatterEditor::parse(content).unwrap();
+        edit(&mut editor);
+        editor.render()
+    }
+
+    const NOTE: &str = r#"---
+# managed by md2pk
+ID: abcde
+Name: "Alex" # shown on discord
+Pronouns:
+  - she/her
+  - 'they/them'
+Proxy Tags: ["A:text", "[text]"]
+Private: false
+---
+# Alex
+
+Body text: with a colon
+---
+more body
+"#;
+
+    #[test]
+    fn roundtrip_is_identical() {
+        assert_eq!(edit(NOTE, |_| {}), NOTE);
+    }
+
+    #[test]
+    fn set_unchanged_values_is_noop() {
+        let out = edit(NOTE, |fm| {
+            assert!(!fm.set("ID", &YamlValue::String(String::from("abcde"))));
+            assert!(!fm.set("Name", &YamlValue::String(String::from("Alex"))));
+            assert!(!fm.set("Private", &YamlValue::Bool(false)));
+            assert!(!fm.set(
+                "Pronouns",
+                &YamlValue::List(vec![String::from("she/her"), String::from("they/them")])
+            ));
+            assert!(!fm.set(
+                "Proxy Tags",
+                &YamlValue::List(vec![String::from("A:text"), String::from("[text]")])
+            ));
+        });
+        assert_eq!(out, NOTE);
+    }
+
+    #[test]
+    fn set_scalar_keeps_quotes_and_comment() {
+        let out = edit(NOTE, |fm| {
+            assert!(fm.set("Name", &YamlValue::String(String::from("Alexandra"))));
+        });
+        assert_eq!(
+            out,
+            NOTE.replace(
+                r#"Name: "Alex" # shown on discord"#,
+                r#"Name: "Alexandra" # shown on discord"#
+            )
+        );
+    }
+
+    #[test]
+    fn set_plain_scalar_quotes_when_needed() {
+        let out = edit(NOTE, |fm| {
+            fm.set("ID", &YamlValue::String(String::from("null")));
+        });
+        assert_eq!(out, NOTE.replace("ID: abcde", r#"ID: "null""#));
+    }
+
+    #[test]
+    fn set_single_quoted_escapes() {
+        let note = "---\nName: 'Alex'\n---\n";
+        let out = edit(note, |fm| {
+            fm.set("Name", &YamlValue::String(String::from("Alex's")));
+        });
+        assert_eq!(out, "---\nName: 'Alex''s'\n---\n");
+    }
+
+    #[test]
+    fn set_bool() {
+        let out = edit(NOTE, |fm| {
+            fm.set("Private", &YamlValue::Bool(true));
+        });
+        assert_eq!(out, NOTE.replace("Private: false", "Private: true"));
+    }
+
+    #[test]
+    fn set_block_list_keeps_indent_and_style() {
+        let out = edit(NOTE, |fm| {
+            fm.set(
+                "Pronouns",
+                &YamlValue::List(vec![String::from("xe/xem"), String::from("it/its")]),
+            );
+        });
+        assert_eq!(
+            out,
+            NOTE.replace(
+                "Pronouns:\n  - she/her\n  - 'they/them'\n",
+                "Pronouns:\n  - xe/xem\n  - it/its\n"
+            )
+        );
+
+        let note = "---\nPronouns:\n- 'she/her'\nName: Alex\n---\n";
+        let out = edit(note, |fm| {
+            fm.set("Pronouns", &YamlValue::List(vec![String::from("it's")]));
+        });
+        assert_eq!(out, "---\nPronouns:\n- 'it''s'\nName: Alex\n---\n");
+    }
+
+    #[test]
+    fn set_flow_list_keeps_style() {
+        let out = edit(NOTE, |fm| {
+            fm.set(
+                "Proxy Tags",
+                &YamlValue::List(vec![String::from("A:text"), String::from("text, A")]),
+            );
+        });
+        assert_eq!(
+            out,
+            NOTE.replace(
+                r#"Proxy Tags: ["A:text", "[text]"]"#,
+                r#"Proxy Tags: ["A:text", "text, A"]"#
+            )
+        );
+
+        let note = "---\nProxy Tags: [A:text]\n---\n";
+        let out = edit(note, |fm| {
+            fm.set(
+                "Proxy Tags",
+                &YamlValue::List(vec![String::from("A:text"), String::from("[text]")]),
+            );
+        });
+        assert_eq!(out, "---\nProxy Tags: [A:text, \"[text]\"]\n---\n");
+    }
+
+    #[test]
+    fn set_new_key_appends() {
+        let out = edit(NOTE, |fm| {
+            fm.set("UUID", &YamlValue::String(String::from("1234-5678")));
+            fm.set("Proxy", &YamlValue::List(vec![String::from("[text]")]));
+        });
+        assert_eq!(
+            out,
+            NOTE.replace(
+                "Private: false\n",
+                "Private: false\nUUID: 1234-5678\nProxy:\n  - \"[text]\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn set_without_frontmatter_creates_it() {
+        let out = edit("# Alex\n\nbody\n", |fm| {
+            fm.set("id", &YamlValue::String(String::from("abcde")));
+        });
+        assert_eq!(out, "---\nid: abcde\n---\n# Alex\n\nbody\n");
+    }
+
+    #[test]
+    fn set_empty_frontmatter() {
+        let out = edit("---\n---\nbody", |fm| {
+            fm.set("id", &YamlValue::String(String::from("abcde")));
+        });
+        assert_eq!(out, "---\nid: abcde\n---\nbody");
+    }
+
+    #[test]
+    fn set_keeps_crlf() {
+        let note = "---\r\nName: Alex\r\nPronouns:\r\n  - she/her\r\n---\r\nbody\r\n";
+        let out = edit(note, |fm| {
+            fm.set(
+                "Pronouns",
+                &YamlValue::List(vec![String::from("she/her"), String::from("they/them")]),
+            );
+            fm.set("id", &YamlValue::String(String::from("abcde")));
+        });
+        assert_eq!(
+            out,
+            "---\r\nName: Alex\r\nPronouns:\r\n  - she/her\r\n  - they/them\r\nid: abcde\r\n---\r\nbody\r\n"
+        );
+    }
+
+    #[test]
+    fn set_quoted_key() {
+        let note = "---\n\"Proxy Tags\":\n  - A:text\nName: Alex\n---\n";
+        let out = edit(note, |fm| {
+            fm.set("Proxy Tags", &YamlValue::List(vec![String::from("B:text")]));
+        });
+        assert_eq!(out, "---\n\"Proxy Tags\":\n  - B:text\nName: Alex\n---\n");
+    }
+
+    #[test]
+    fn list_to_scalar_and_back() {
+        let note = "---\nPronouns:\n  - she/her\n# trailing comment\nName: Alex\n---\n";
+        let out = edit(note, |fm| {
+            fm.set("Pronouns", &YamlValue::String(String::from("she/her")));
+        });
+        assert_eq!(
+            out,
+            "---\nPronouns: she/her\n# trailing comment\nName: Alex\n---\n"
+        );
+
+        let out = edit(&out, |fm| {
+            fm.set("Pronouns", &YamlValue::List(vec![String::from("she/her")]));
+        });
+        assert_eq!(
+            out,
+            "---\nPronouns:\n  - she/her\n# trailing comment\nName: Alex\n---\n"
+        );
+    }
+
+    #[test]
+    fn set_nested_paths() {
+        let note =
+            "---\nName: Alex\npluralkit:\n    id: abcde\n    proxy:\n      - 'A:text'\n---\n";
+        let out = edit(note, |fm| {
+            let id = YamlValue::String(String::from("fghij"));
+            assert!(fm.set_field("pluralkit.id", &id, false).unwrap());
+            let tag = YamlValue::String(String::from("B:text"));
+            assert!(fm.set_field("/pluralkit/proxy/0", &tag, false).unwrap());
+            assert!(fm.set_field("pluralkit.proxy.1", &tag, false).unwrap());
+            let uuid = YamlValue::String(String::from("1234"));
+            assert!(fm.set_field("pluralkit.uuid", &uuid, false).unwrap());
+        });
+        assert_eq!(
+            out,
+            "---\nName: Alex\npluralkit:\n    id: fghij\n    proxy:\n      - 'B:text'\n      - 'B:text'\n    uuid: \"1234\"\n---\n"
+        );
+    }
+
+    #[test]
+    fn set_single_segment_pointer() {
+        let out = edit("---\nID: abcde\nid: abcde\n---\n", |fm| {
+            let id = YamlValue::String(String::from("fghij"));
+            assert!(fm.set_field("/id", &id, false).unwrap());
+            assert_eq!(fm.resolve_key(&FieldKeys::from("/id"), false), "/id");
+        });
+        assert_eq!(out, "---\nID: abcde\nid: fghij\n---\n");
+    }
+
+    #[test]
+    fn set_nested_path_creates_maps() {
+        let out = edit("---\nName: Alex\n---\n", |fm| {
+            let id = YamlValue::String(String::from("abcde"));
+            assert!(fm.set_field("pluralkit.ids.member", &id, false).unwrap());
+        });
+        assert_eq!(
+            out,
+            "---\nName: Alex\npluralkit:\n  ids:\n    member: abcde\n---\n"
+        );
+    }
+
+    #[test]
+    fn set_nested_path_into_inline_value_errors() {
+        edit("---\npluralkit: abcde\n---\n", |fm| {
+            let id = YamlValue::String(String::from("abcde"));
+            assert!(fm.set_field("pluralkit.id", &id, false).is_err());
+        });
+    }
+
+    #[test]
+    fn unterminated_frontmatter_errors() {
+        assert!(FrontmatterEditor::parse("---\nid: abcde\n").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use eyre::eyre;
+use pkrs_fork::{client::PkClient, model::PkId};
+
+use crate::{
+    config::{Config, DescriptionSource, FieldKeys},
+    frontmatter::{FrontmatterEditor, YamlValue},
+    get_files,
+    pull::{group_values, member_values},
+};
+
+/// Replace characters that aren't valid in file names on common platforms
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    format!("{}.md", name.trim())
+}
+
+/// Path of a new note for `name` in `dir`, unique among the notes created in this run
+///
+/// Names that clash, ignoring case for case-insensitive file systems, get the id appended.
+fn note_path(dir: &Path, name: &str, id: &str, planned: &mut HashSet<String>) -> PathBuf {
+    let key = |path: &Path| path.to_string_lossy().to_lowercase();
+
+    let mut path = dir.join(file_name(name));
+    if planned.contains(&key(&path)) {
+        path = dir.join(file_name(&format!("{name} ({id})")));
+    }
+    planned.insert(key(&path));
+    path
+}
+
+/// Note body holding `description` where `source` reads it from, field descriptions are part of
+/// the frontmatter values instead
+fn description_body(
+    source: &DescriptionSource,
+    heading: Option<&String>,
+    description: Option<&str>,
+) -> Option<String> {
+    let description = description?;
+    match source {
+        DescriptionSource::Body => Some(String::from(description)),
+        DescriptionSource::Heading => heading.map(|heading| format!("{heading}\n\n{description}")),
+        DescriptionSource::None | DescriptionSource::Field => None,
+    }
+}
+
+fn render_note(
+    values: &[(FieldKeys, YamlValue)],
+    tags: Option<&HashSet<String>>,
+    body: Option<&str>,
+) -> eyre::Result<String> {
+    let mut editor = FrontmatterEditor::parse("")?;
+    for (keys, value) in values {
+        editor.set_field(keys.primary(), value, false)?;
+    }
+
+    if let Some(tags) = tags {
+        let mut tags: Vec<String> = tags.iter().cloned().collect();
+        tags.sort();
+        editor.set("tags", &YamlValue::List(tags));
+    }
+
+    let mut content = editor.render();
+    if let Some(body) = body {
+        content.push_str(body);
+        if !body.ends_with('\n') {
+            content.push('\n');
+        }
+    }
+
+    Ok(content)
+}
+
+/// Directory new notes are created in, and the tags they get
+struct Target<'a> {
+    dir: PathBuf,
+    tags: Option<&'a HashSet<String>>,
+}
+
+/// Import targets for members and groups
+fn targets(conf: &Config) -> eyre::Result<(Target<'_>, Target<'_>)> {
+    match conf.scan_type.as_str() {
+        "path" => {
+            let Some(member_dir) = &conf.path_scanner.member_dir else {
+                return Err(eyre!("`path_scanner.member_dir` needs to be set to import"));
+            };
+            let Some(group_dir) = &conf.path_scanner.group_dir else {
+                return Err(eyre!("`path_scanner.group_dir` needs to be set to import"));
+            };
+
+            Ok((
+                Target {
+                    dir: PathBuf::from(shellexpand::tilde(member_dir).as_ref()),
+                    tags: None,
+                },
+                Target {
+                    dir: PathBuf::from(shellexpand::tilde(group_dir).as_ref()),
+                    tags: None,
+                },
+            ))
+        }
+        "tags" => {
+            let root_dir = PathBuf::from(shellexpand::tilde(&conf.tag_scanner.root_dir).as_ref());
+            Ok((
+                Target {
+                    dir: root_dir.clone(),
+                    tags: conf.tag_scanner.member_tags.as_ref(),
+                },
+                Target {
+                    dir: root_dir,
+                    tags: conf.tag_scanner.group_tags.as_ref(),
+                },
+            ))
+        }
+        _ => Err(eyre!(
+            "Unknown `scan_type` '{}' valid options are `tags` and `path`",
+            conf.scan_type
+        )),
+    }
+}
+
+fn write_note(kind: &str, id: &str, path: &Path, content: &str, execute: bool) -> bool {
+    if path.exists() {
+        println!(
+            "WARN  {}: file already exists, skipping {kind} {id}",
+            path.display()
+        );
+        return false;
+    }
+
+    println!("CREATE {kind} {id} ({})", path.display());
+    if !execute {
+        return true;
+    }
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(path, content));
+
+    if let Err(err) = written {
+        println!("ERROR {}: {err}", path.display());
+        return false;
+    }
+
+    true
+}
+
+pub(crate) async fn run(pk: &PkClient, conf: &Config, execute: bool) -> eyre::Result<()> {
+    let system = pk.get_system(&PkId("@me".into())).await?;
+    println!(
+        "Importing System: {} ...",
+        system.name.as_deref().unwrap_or(&system.id.0)
+    );
+
+    let (member_target, group_target) = targets(conf)?;
+
+    let files = get_files(conf)?;
+    let known_members: HashSet<&str> = files
+        .members
+        .iter()
+        .filter_map(|member| member.id.as_ref().map(AsRef::as_ref))
+        .collect();
+    let known_groups: HashSet<&str> = files.groups.iter().map(|group| group.id.as_ref()).collect();
+
+    let mut created = 0;
+    let mut planned = HashSet::new();
+    for member in pk.get_system_members(&system.id).await? {
+        if known_members.contains(member.id.0.as_str()) {
+            continue;
+        }
+
+        // new notes have nothing to clobber yet, so fields markdown owns are seeded too
+        let values = member_values(&conf.fields.member, &conf.name, |_| true, &member);
+        let body = description_body(
+            &conf.fields.member.description_source,
+            conf.fields.member.description_heading.as_ref(),
+            member.description.as_deref(),
+        );
+        let content = render_note(&values, member_target.tags, body.as_deref())?;
+        let path = note_path(&member_target.dir, &member.name, &member.id.0, &mut planned);
+        created += usize::from(write_note("member", &member.id.0, &path, &content, execute));
+    }
+
+    for group in pk.get_system_groups(&system.id).await? {
+        if known_groups.contains(group.id.0.as_str()) {
+            continue;
+        }
+
+        let values = group_values(&conf.fields.group, |_| true, &group);
+        let body = description_body(
+            &conf.fields.group.description_source,
+            conf.fields.group.description_heading.as_ref(),
+            group.description.as_deref(),
+        );
+        let content = render_note(&values, group_target.tags, body.as_deref())?;
+        let path = note_path(&group_target.dir, &group.name, &group.id.0, &mut planned);
+        created += usize::from(write_note("group", &group.id.0, &path, &content, execute));
+    }
+
+    println!();
+    println!("{created} note(s) to create");
+
+    if !execute {
+        println!("Dry run, pass `--execute` to apply changes");
+    }
+
+    Ok(())
+}
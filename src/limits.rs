@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::config::LengthStrategy;
+
+pub(crate) const NAME: usize = 100;
+pub(crate) const DISPLAY_NAME: usize = 100;
+pub(crate) const PRONOUNS: usize = 100;
+pub(crate) const DESCRIPTION: usize = 1000;
+pub(crate) const PROXY_TAG: usize = 100;
+
+const ELLIPSIS: char = '…';
+
+/// Length the way pluralkit counts it, in UTF-16 code units
+pub(crate) fn length(value: &str) -> usize {
+    value.encode_utf16().count()
+}
+
+/// Byte offset of the longest prefix of `value` that's at most `max` long
+fn prefix_end(value: &str, max: usize) -> usize {
+    let mut len = 0;
+    for (idx, c) in value.char_indices() {
+        len += c.len_utf16();
+        if len > max {
+            return idx;
+        }
+    }
+
+    value.len()
+}
+
+fn truncate_word(value: &str, max: usize) -> String {
+    let end = prefix_end(value, max - ELLIPSIS.len_utf16());
+    let cut = &value[..end];
+
+    // only back off to a word boundary if we actually cut through a word
+    let cut = if value[end..].starts_with(char::is_whitespace) {
+        cut
+    } else {
+        cut.rfind(char::is_whitespace)
+            .filter(|idx| *idx > 0)
+            .map_or(cut, |idx| &cut[..idx])
+    };
+
+    format!("{}{ELLIPSIS}", cut.trim_end())
+}
+
+fn truncate_paragraph(value: &str, max: usize) -> String {
+    let end = prefix_end(value, max);
+    match value[..end].rfind("\n\n") {
+        Some(idx) if !value[..idx].trim().is_empty() => String::from(value[..idx].trim_end()),
+        _ => truncate_word(value, max),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Violation {
+    Truncated {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+    TooLong {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+}
+
+impl Violation {
+    pub(crate) fn is_error(&self) -> bool {
+        matches!(self, Self::TooLong { .. })
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { field, len, max } => {
+                write!(f, "`{field}` is {len} characters long, truncated to {max}")
+            }
+            Self::TooLong { field, len, max } => write!(
+                f,
+                "`{field}` is {len} characters long, PluralKit allows at most {max}"
+            ),
+        }
+    }
+}
+
+/// Check `value` against `max`, truncating it in place if `strategy` allows
+pub(crate) fn enforce(
+    field: &'static str,
+    value: &mut String,
+    max: usize,
+    strategy: LengthStrategy,
+) -> Option<Violation> {
+    let len = length(value);
+    if len <= max {
+        return None;
+    }
+
+    *value = match strategy {
+        LengthStrategy::Error => return Some(Violation::TooLong { field, len, max }),
+        LengthStrategy::TruncateWord => truncate_word(value, max),
+        LengthStrategy::TruncateParagraph => truncate_paragraph(value, max),
+    };
+
+    Some(Violation::Truncated { field, len, max })
+}
+
+/// Check `value` against `max` for fields that can't be truncated
+pub(crate) fn check(field: &'static str, value: &str, max: usize) -> Option<Violation> {
+    let len = length(value);
+    (len > max).then_some(Violation::TooLong { field, len, max })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_word_backs_off_to_word_boundary() {
+        assert_eq!(truncate_word("hello world foo", 12), "hello world…");
+        assert_eq!(truncate_word("hello wonderful", 10), "hello…");
+    }
+
+    #[test]
+    fn truncate_word_cuts_single_oversized_word() {
+        assert_eq!(truncate_word("abcdefghij", 5), "abcd…");
+    }
+
+    #[test]
+    fn truncate_paragraph_keeps_whole_paragraphs() {
+        assert_eq!(
+            truncate_paragraph("first para\n\nsecond para long", 20),
+            "first para"
+        );
+        // a first paragraph that's too long falls back to cutting words
+        assert_eq!(
+            truncate_paragraph("first long paragraph\n\nsecond", 12),
+            "first long…"
+        );
+    }
+
+    #[test]
+    fn lengths_are_counted_in_utf16() {
+        assert_eq!(length("😀😀😀"), 6);
+        assert!(check("name", "😀😀😀", 5).is_some());
+        assert!(check("name", "ééééé", 5).is_none());
+
+        let mut value = String::from("😀😀😀");
+        let violation = enforce("name", &mut value, 5, LengthStrategy::TruncateWord);
+        assert!(matches!(
+            violation,
+            Some(Violation::Truncated { len: 6, .. })
+        ));
+        assert_eq!(value, "😀😀…");
+        assert_eq!(length(&value), 5);
+    }
+
+    #[test]
+    fn error_strategy_leaves_value_alone() {
+        let mut value = String::from("too long");
+        let violation = enforce("name", &mut value, 3, LengthStrategy::Error);
+        assert!(violation.is_some_and(|violation| violation.is_error()));
+        assert_eq!(value, "too long");
+    }
+}
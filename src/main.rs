@@ -1,30 +1,103 @@
 use clap::Parser;
 use color_eyre::eyre::Result;
 use eyre::eyre;
-use pkrs_fork::{client::PkClient, model::PkId};
-use std::fs;
+use pkrs_fork::client::PkClient;
+use std::{fs, path::Path};
 use tabled::{builder::Builder, settings::Style};
 
 use crate::{
     config::{Command, CommandLine, Config},
+    limits::Violation,
     scan_result::ScanResult,
 };
 
 mod config;
+mod diff;
+mod discord_markdown;
+mod fields;
+mod frontmatter;
+mod import;
+mod limits;
 mod markdown;
 mod markdown_objects;
+mod membership;
+mod name_template;
+mod privacy;
+mod proxy_tags;
+mod pull;
 mod scan_result;
 mod scanner_paths;
 mod scanner_tags;
+mod snapshot;
+mod sync;
+mod sync_directive;
+
+/// Print limit violations for the file at `path`, returns whether it can still be synced
+fn report_violations(path: &Path, violations: &[Violation]) -> bool {
+    for violation in violations {
+        if violation.is_error() {
+            println!("ERROR {}: {violation}", path.display());
+        } else {
+            println!("WARN  {}: {violation}", path.display());
+        }
+    }
+
+    !violations.iter().any(Violation::is_error)
+}
 
 fn get_files(conf: &Config) -> eyre::Result<ScanResult> {
-    match conf.scan_type.as_str() {
-        "tags" => scanner_tags::run(&conf.tag_scanner, &conf.fields),
-        "path" => scanner_paths::run(&conf.path_scanner, &conf.fields),
-        _ => Err(eyre!(
-            "Unknown `scan_type` '{}' valid options are `tags` and `path`",
-            conf.scan_type
-        )),
+    let mut files = match conf.scan_type.as_str() {
+        "tags" => scanner_tags::run(&conf.tag_scanner, &conf.fields, conf.membership.mode)?,
+        "path" => scanner_paths::run(&conf.path_scanner, &conf.fields)?,
+        _ => {
+            return Err(eyre!(
+                "Unknown `scan_type` '{}' valid options are `tags` and `path`",
+                conf.scan_type
+            ));
+        }
+    };
+
+    files.members.retain_mut(|member| {
+        if let Some(template) = name_template::template(&conf.name) {
+            match name_template::render(template, member) {
+                Ok(Some(display_name)) => member.display_name = Some(display_name),
+                Ok(None) => {}
+                Err(err) => {
+                    println!("ERROR {}: {err}", member.path.display());
+                    return false;
+                }
+            }
+        }
+
+        let violations = member.enforce_limits(&conf.limits);
+        report_violations(&member.path, &violations)
+    });
+    files.groups.retain_mut(|group| {
+        let violations = group.enforce_limits(&conf.limits);
+        report_violations(&group.path, &violations)
+    });
+
+    Ok(files)
+}
+
+fn flag(value: Option<bool>) -> &'static str {
+    value.map_or_else(|| "❔", |v| if v { "✔️" } else { "❌" })
+}
+
+/// Print the frontmatter key every field of a note was read from
+fn print_field_keys(path: &Path, keys: &[(String, String)]) {
+    println!("FIELDS {}", path.display());
+    for (field, key) in keys {
+        println!("  {field}: `{key}`");
+    }
+}
+
+fn pk_client(conf: &Config) -> PkClient {
+    PkClient {
+        // TODO: Embed version
+        user_agent: "md2pk-rs VERSION".to_string(),
+        token: conf.token.clone(),
+        ..Default::default()
     }
 }
 
@@ -46,37 +119,72 @@ async fn main() -> Result<()> {
     }
 
     let conf = Config::load(&cli)?;
+    conf.ownership.validate()?;
     match &cli.command {
-        Command::Sync { execute } => {
-            let pk = PkClient {
-                // TODO: Embed version
-                user_agent: "md2pk-rs VERSION".to_string(),
-                token: conf.token,
-                ..Default::default()
-            };
-
-            let system = pk.get_system(&PkId("@me".into())).await?;
-
-            println!("Syncing System: {} ...", system.name.unwrap_or(system.id.0));
-
-            Ok(())
+        Command::Sync {
+            execute,
+            allow_proxy_collisions,
+            interactive,
+        } => {
+            sync::run(
+                &pk_client(&conf),
+                &conf,
+                cli.quiet,
+                *execute,
+                *allow_proxy_collisions,
+                *interactive,
+            )
+            .await
         }
-        Command::List => {
+        Command::Import { execute } => import::run(&pk_client(&conf), &conf, *execute).await,
+        Command::Pull { execute } => pull::run(&pk_client(&conf), &conf, cli.quiet, *execute).await,
+        Command::List { verbose } => {
             let files = get_files(&conf)?;
+            if *verbose {
+                for group in &files.groups {
+                    print_field_keys(&group.path, &group.field_keys);
+                }
+                for member in &files.members {
+                    print_field_keys(&member.path, &member.field_keys);
+                }
+                println!();
+            }
+
             let mut group_builder = Builder::new();
             let total_groups = files.groups.len();
-            group_builder.push_record(["ID", "UUID", "Name", "Display Name", "Prv"]);
+            group_builder.push_record([
+                "ID",
+                "UUID",
+                "Name",
+                "Display Name",
+                "Prv",
+                "Color",
+                "Description",
+                "Images",
+                "Sync",
+            ]);
             for group in files.groups {
+                let images: Vec<&str> = [("icon", &group.icon), ("banner", &group.banner)]
+                    .into_iter()
+                    .filter_map(|(label, url)| url.as_ref().map(|_| label))
+                    .collect();
+
                 group_builder.push_record([
                     group.id.into(),
                     group.uuid.map(String::from).unwrap_or_default(),
                     group.name.unwrap_or_default(),
                     group.display_name.unwrap_or_default(),
-                    String::from(
-                        group
-                            .private
-                            .map_or_else(|| "❔", |v| if v { "✔️" } else { "❌" }),
-                    ),
+                    String::from(flag(group.privacy.get("visibility"))),
+                    group
+                        .color
+                        .map(|color| format!("#{color}"))
+                        .unwrap_or_default(),
+                    group
+                        .description
+                        .map(|description| format!("{} chars", limits::length(&description)))
+                        .unwrap_or_default(),
+                    images.join("\n"),
+                    group.sync.to_string(),
                 ]);
             }
             group_builder.push_record(["", "", "", "", "", &format!("Total: {total_groups}")]);
@@ -96,20 +204,63 @@ async fn main() -> Result<()> {
                 "Prv",
                 "Pronouns",
                 "Proxy Tags",
+                "Color",
+                "Birthday",
+                "Description",
+                "Images",
+                "Options",
+                "Groups",
+                "Sync",
             ]);
             for member in files.members {
+                let images: Vec<&str> = [
+                    ("avatar", &member.avatar_url),
+                    ("webhook avatar", &member.webhook_avatar_url),
+                    ("banner", &member.banner),
+                ]
+                .into_iter()
+                .filter_map(|(label, url)| url.as_ref().map(|_| label))
+                .collect();
+
+                let options: Vec<String> = [
+                    ("keep proxy", member.keep_proxy),
+                    ("tts", member.tts),
+                    ("autoproxy", member.autoproxy_enabled),
+                ]
+                .into_iter()
+                .filter(|(_, value)| value.is_some())
+                .map(|(label, value)| format!("{label}: {}", flag(value)))
+                .collect();
+
                 member_builder.push_record([
-                    member.id.into(),
+                    member.id.map(String::from).unwrap_or_default(),
                     member.uuid.map(String::from).unwrap_or_default(),
                     member.name.unwrap_or_default(),
                     member.display_name.unwrap_or_default(),
-                    String::from(
-                        member
-                            .private
-                            .map_or_else(|| "❔", |v| if v { "✔️" } else { "❌" }),
-                    ),
+                    String::from(flag(member.privacy.get("visibility"))),
                     member.pronouns.join("\n"),
-                    member.proxy_tags.join("\n"),
+                    member
+                        .proxy_tags
+                        .iter()
+                        .map(|tag| tag.format(&conf.fields.member.proxy_tag_placeholder))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    member
+                        .color
+                        .map(|color| format!("#{color}"))
+                        .unwrap_or_default(),
+                    member
+                        .birthday
+                        .map(|date| date.to_string())
+                        .unwrap_or_default(),
+                    member
+                        .description
+                        .map(|description| format!("{} chars", limits::length(&description)))
+                        .unwrap_or_default(),
+                    images.join("\n"),
+                    options.join("\n"),
+                    member.groups.unwrap_or_default().join("\n"),
+                    member.sync.to_string(),
                 ]);
             }
             member_builder.push_record(["", "", "", "", "", &format!("Total: {total_members}")]);
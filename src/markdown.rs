@@ -32,3 +32,72 @@ pub(crate) fn walker(
         }
     })
 }
+
+/// Level of a markdown heading line, `None` if it's not a heading
+fn heading_level(line: &str) -> Option<usize> {
+    let line = line.trim();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    ((1..=6).contains(&level) && line[level..].chars().next().is_none_or(char::is_whitespace))
+        .then_some(level)
+}
+
+/// Content below `heading` up to the next heading of the same or a higher level
+pub(crate) fn section<'a>(content: &'a str, heading: &str) -> Option<&'a str> {
+    let level = heading_level(heading)?;
+
+    let mut in_fence = false;
+    let mut start = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        match start {
+            None if line.trim() == heading.trim() => start = Some(offset),
+            Some(start) if heading_level(line).is_some_and(|line_level| line_level <= level) => {
+                return Some(content[start..line_start].trim()).filter(|s| !s.is_empty());
+            }
+            _ => {}
+        }
+    }
+
+    start
+        .map(|start| content[start..].trim())
+        .filter(|s| !s.is_empty())
+}
+
+/// `[[links]]` in the bullet points of a markdown list, embeds are skipped
+pub(crate) fn list_links(content: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    for line in content.lines() {
+        let Some(item) = line
+            .trim_start()
+            .strip_prefix(['-', '*', '+'])
+            .filter(|item| item.starts_with(char::is_whitespace))
+        else {
+            continue;
+        };
+
+        let mut rest = item;
+        while let Some(start) = rest.find("[[") {
+            let Some(end) = rest[start..].find("]]") else {
+                break;
+            };
+
+            if !rest[..start].ends_with('!') {
+                links.push(String::from(&rest[start..start + end + 2]));
+            }
+            rest = &rest[start + end + 2..];
+        }
+    }
+
+    links
+}
@@ -1,8 +1,19 @@
 use std::path::{Path, PathBuf};
 
-use crate::config::{GroupFieldConfig, MemberFieldConfig};
+use crate::{
+    config::{DescriptionSource, FieldKeys, GroupFieldConfig, LimitConfig, MemberFieldConfig},
+    discord_markdown,
+    fields::{self, FieldReader},
+    limits,
+    limits::Violation,
+    markdown,
+    privacy::{self, PrivacySettings},
+    proxy_tags::MarkdownProxyTag,
+    sync_directive::{self, SyncDirective},
+};
 use eyre::eyre;
 use frontmatter_gen::Frontmatter;
+use time::{Date, Month};
 
 #[derive(Debug)]
 pub(crate) struct PluralKitID(String);
@@ -17,6 +28,11 @@ impl From<PluralKitID> for String {
         value.0
     }
 }
+impl AsRef<str> for PluralKitID {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct PluralKitUUID(String);
@@ -31,17 +47,155 @@ impl From<PluralKitUUID> for String {
         value.0
     }
 }
+impl AsRef<str> for PluralKitUUID {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Read the description from the configured `source`
+fn parse_description(
+    source: &DescriptionSource,
+    heading: Option<&String>,
+    field: Option<&FieldKeys>,
+    fields: &FieldReader,
+    content: &str,
+) -> eyre::Result<Option<String>> {
+    let description = match source {
+        DescriptionSource::None => None,
+        DescriptionSource::Body => Some(discord_markdown::from_obsidian(content)),
+        DescriptionSource::Heading => {
+            let Some(heading) = heading else {
+                return Err(eyre!(
+                    "`description_source` is `heading` but `description_heading` isn't set"
+                ));
+            };
+            markdown::section(content, heading).map(discord_markdown::from_obsidian)
+        }
+        DescriptionSource::Field => fields.string(field),
+    };
+
+    Ok(description.filter(|description| !description.is_empty()))
+}
+
+pub(crate) fn parse_url(
+    field: Option<&FieldKeys>,
+    value: Option<&str>,
+) -> eyre::Result<Option<String>> {
+    match value {
+        None => Ok(None),
+        Some(url) if url.starts_with("https://") || url.starts_with("http://") => {
+            Ok(Some(String::from(url)))
+        }
+        Some(url) => Err(eyre!(
+            "`{}` must be a http(s) url, got `{url}`",
+            field.map(FieldKeys::primary).unwrap_or_default()
+        )),
+    }
+}
+
+/// Parse a hex color, with or without a leading `#`, into pluralkit's lowercase format
+pub(crate) fn parse_color(
+    field: Option<&FieldKeys>,
+    value: Option<&str>,
+) -> eyre::Result<Option<String>> {
+    let Some(color) = value else {
+        return Ok(None);
+    };
+
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(eyre!(
+            "`{}` must be a 6 digit hex color, got `{color}`",
+            field.map(FieldKeys::primary).unwrap_or_default()
+        ));
+    }
+
+    Ok(Some(hex.to_ascii_lowercase()))
+}
+
+/// Parse `YYYY-MM-DD` or `MM-DD`, dates without a year use year 4 like pluralkit does
+pub(crate) fn parse_birthday(
+    field: Option<&FieldKeys>,
+    value: Option<&str>,
+) -> eyre::Result<Option<Date>> {
+    let Some(birthday) = value else {
+        return Ok(None);
+    };
+
+    let parts: Vec<&str> = birthday.split('-').collect();
+    let (year, month, day) = match parts.as_slice() {
+        [year, month, day] => (year.parse().ok(), month.parse().ok(), day.parse().ok()),
+        [month, day] => (Some(4), month.parse().ok(), day.parse().ok()),
+        _ => (None, None, None),
+    };
+
+    year.zip(month.and_then(|month: u8| Month::try_from(month).ok()))
+        .zip(day)
+        .and_then(|((year, month), day)| Date::from_calendar_date(year, month, day).ok())
+        .map(Some)
+        .ok_or_else(|| {
+            eyre!(
+                "`{}` must be a date formatted as `YYYY-MM-DD` or `MM-DD`, got `{birthday}`",
+                field.map(FieldKeys::primary).unwrap_or_default()
+            )
+        })
+}
+
+fn parse_proxy_tags(
+    fields: &FieldReader,
+    field: Option<&FieldKeys>,
+    placeholder: &str,
+) -> eyre::Result<Vec<MarkdownProxyTag>> {
+    let field_name = field.map(FieldKeys::primary).unwrap_or_default();
+    fields
+        .list(field)
+        .unwrap_or_default()
+        .iter()
+        .map(|tag| {
+            MarkdownProxyTag::parse(tag, placeholder).map_err(|err| eyre!("`{field_name}`: {err}"))
+        })
+        .collect()
+}
+
+/// Coerce a required or optional id field, values that aren't text are an error
+fn parse_id(fields: &FieldReader, field: &FieldKeys) -> eyre::Result<Option<String>> {
+    let Some((field, value)) = fields.value(Some(field)) else {
+        return Ok(None);
+    };
+
+    match fields::to_string(value) {
+        Some(id) => Ok((!id.is_empty()).then_some(id)),
+        None => Err(eyre!("couldn't parse id field `{field}` as string")),
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct MarkdownMember {
     pub(crate) path: PathBuf,
-    pub(crate) id: PluralKitID,
+    /// `None` for notes that don't exist on PluralKit yet
+    pub(crate) id: Option<PluralKitID>,
     pub(crate) uuid: Option<PluralKitUUID>,
     pub(crate) name: Option<String>,
     pub(crate) display_name: Option<String>,
     pub(crate) pronouns: Vec<String>,
-    pub(crate) proxy_tags: Vec<String>,
-    pub(crate) private: Option<bool>,
+    pub(crate) proxy_tags: Vec<MarkdownProxyTag>,
+    pub(crate) privacy: PrivacySettings,
+    pub(crate) description: Option<String>,
+    pub(crate) avatar_url: Option<String>,
+    pub(crate) webhook_avatar_url: Option<String>,
+    pub(crate) banner: Option<String>,
+    pub(crate) color: Option<String>,
+    pub(crate) birthday: Option<Date>,
+    pub(crate) keep_proxy: Option<bool>,
+    pub(crate) tts: Option<bool>,
+    pub(crate) autoproxy_enabled: Option<bool>,
+    /// `None` if the note doesn't declare groups, memberships are left alone then
+    pub(crate) groups: Option<Vec<String>>,
+    pub(crate) aliases: Vec<String>,
+    pub(crate) sync: SyncDirective,
+    /// configured key and the frontmatter key each field was read from
+    pub(crate) field_keys: Vec<(String, String)>,
 }
 
 impl MarkdownMember {
@@ -51,83 +205,160 @@ impl MarkdownMember {
         content: &str,
         cfg: &MemberFieldConfig,
     ) -> eyre::Result<Self> {
-        let Some(id_val) = frontmatter.get(&cfg.id) else {
-            return Err(eyre!("couldn't find id field `{}`", cfg.id));
-        };
-        let Some(id_str) = id_val.as_str() else {
-            return Err(eyre!("couldn't find id field `{}`", cfg.id));
-        };
+        let fields = FieldReader::new(path, frontmatter, cfg.case_insensitive);
+
+        let id = parse_id(&fields, &cfg.id)?.map(|id| PluralKitID::from(id.as_str()));
 
-        let uuid = cfg
-            .uuid
-            .as_ref()
-            .and_then(|uuid_field| frontmatter.get(uuid_field))
-            .and_then(|uuid_val| uuid_val.as_str())
-            .map(PluralKitUUID::from);
-
-        let name = cfg
-            .name
-            .as_ref()
-            .and_then(|name_field| frontmatter.get(name_field))
-            .and_then(|name_val| name_val.as_str())
-            .map(|str| str.to_owned())
-            .or_else(|| {
-                path.file_stem()
-                    .map(|stem| String::from(stem.to_string_lossy()))
-            });
-
-        let display_name = cfg
-            .display_name
-            .as_ref()
-            .and_then(|display_name_field| frontmatter.get(display_name_field))
-            .and_then(|display_name_val| display_name_val.as_str())
-            .map(|str| str.to_owned());
-
-        let private = cfg
-            .private
-            .as_ref()
-            .and_then(|private_field| frontmatter.get(private_field))
-            .and_then(|private_val| private_val.as_bool());
-
-        let pronouns: Vec<String> = cfg
-            .pronouns
-            .as_ref()
-            .and_then(|pronouns_field| frontmatter.get(pronouns_field))
-            .and_then(|pronouns_val| pronouns_val.as_array())
-            .and_then(|pronouns_array| {
-                pronouns_array
-                    .iter()
-                    .map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
-
-        let proxy_tags: Vec<String> = cfg
-            .proxy_tags
-            .as_ref()
-            .and_then(|proxy_tags_field| frontmatter.get(proxy_tags_field))
-            .and_then(|proxy_tags_val| proxy_tags_val.as_array())
-            .and_then(|proxy_tags_array| {
-                proxy_tags_array
-                    .iter()
-                    .map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
+        let uuid = fields
+            .string(cfg.uuid.as_ref())
+            .map(|uuid| PluralKitUUID::from(uuid.as_str()));
+
+        let name = fields.string(cfg.name.as_ref()).or_else(|| {
+            path.file_stem()
+                .map(|stem| String::from(stem.to_string_lossy()))
+        });
+
+        let display_name = fields.string(cfg.display_name.as_ref());
+
+        let privacy = PrivacySettings::parse(
+            &fields,
+            cfg.private.as_ref(),
+            cfg.privacy.as_ref(),
+            privacy::MEMBER_KEYS,
+        )?;
+
+        let pronouns = fields.list(cfg.pronouns.as_ref()).unwrap_or_default();
+
+        let proxy_tags =
+            parse_proxy_tags(&fields, cfg.proxy_tags.as_ref(), &cfg.proxy_tag_placeholder)?;
+
+        let description = parse_description(
+            &cfg.description_source,
+            cfg.description_heading.as_ref(),
+            cfg.description.as_ref(),
+            &fields,
+            content,
+        )?;
+
+        let avatar_url = parse_url(
+            cfg.avatar_url.as_ref(),
+            fields.string(cfg.avatar_url.as_ref()).as_deref(),
+        )?;
+        let webhook_avatar_url = parse_url(
+            cfg.webhook_avatar_url.as_ref(),
+            fields.string(cfg.webhook_avatar_url.as_ref()).as_deref(),
+        )?;
+        let banner = parse_url(
+            cfg.banner.as_ref(),
+            fields.string(cfg.banner.as_ref()).as_deref(),
+        )?;
+        let color = parse_color(
+            cfg.color.as_ref(),
+            fields.string(cfg.color.as_ref()).as_deref(),
+        )?;
+        let birthday = parse_birthday(
+            cfg.birthday.as_ref(),
+            fields.string(cfg.birthday.as_ref()).as_deref(),
+        )?;
 
         Ok(Self {
-            id: PluralKitID::from(id_str),
+            id,
             uuid,
             name,
             display_name,
             pronouns,
             proxy_tags,
-            private,
+            privacy,
+            description,
+            avatar_url,
+            webhook_avatar_url,
+            banner,
+            color,
+            birthday,
+            keep_proxy: fields.bool(cfg.keep_proxy.as_ref()),
+            tts: fields.bool(cfg.tts.as_ref()),
+            autoproxy_enabled: fields.bool(cfg.autoproxy_enabled.as_ref()),
+            groups: fields.list(cfg.groups.as_ref()),
+            aliases: fields.list(cfg.aliases.as_ref()).unwrap_or_default(),
+            sync: SyncDirective::parse(&fields, cfg.sync.as_ref(), sync_directive::MEMBER_FIELDS)?,
             path: PathBuf::from(path),
+            field_keys: fields.into_used_keys(),
         })
     }
 }
 
+impl MarkdownMember {
+    /// Check fields against pluralkit's length limits, truncating them where configured
+    pub(crate) fn enforce_limits(&mut self, cfg: &LimitConfig) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        if let Some(name) = &mut self.name {
+            violations.extend(limits::enforce("name", name, limits::NAME, cfg.name));
+        }
+        if let Some(display_name) = &mut self.display_name {
+            violations.extend(limits::enforce(
+                "display_name",
+                display_name,
+                limits::DISPLAY_NAME,
+                cfg.display_name,
+            ));
+        }
+        if let Some(description) = &mut self.description {
+            violations.extend(limits::enforce(
+                "description",
+                description,
+                limits::DESCRIPTION,
+                cfg.description,
+            ));
+        }
+
+        violations.extend(limits::check(
+            "pronouns",
+            &self.pronouns.join(", "),
+            limits::PRONOUNS,
+        ));
+        for proxy_tag in &self.proxy_tags {
+            violations.extend(limits::check(
+                "proxy_tags",
+                &proxy_tag.tag_text(),
+                limits::PROXY_TAG,
+            ));
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+impl MarkdownMember {
+    /// Member note `members/{name}.md` with only a name set
+    pub(crate) fn named(name: &str) -> Self {
+        Self {
+            path: PathBuf::from(format!("members/{name}.md")),
+            id: None,
+            uuid: None,
+            name: Some(String::from(name)),
+            display_name: None,
+            pronouns: Vec::new(),
+            proxy_tags: Vec::new(),
+            privacy: PrivacySettings::default(),
+            description: None,
+            avatar_url: None,
+            webhook_avatar_url: None,
+            banner: None,
+            color: None,
+            birthday: None,
+            keep_proxy: None,
+            tts: None,
+            autoproxy_enabled: None,
+            groups: None,
+            aliases: Vec::new(),
+            sync: SyncDirective::default(),
+            field_keys: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct MarkdownGroup {
     pub(crate) path: PathBuf,
@@ -135,7 +366,16 @@ pub(crate) struct MarkdownGroup {
     pub(crate) uuid: Option<PluralKitUUID>,
     pub(crate) name: Option<String>,
     pub(crate) display_name: Option<String>,
-    pub(crate) private: Option<bool>,
+    pub(crate) privacy: PrivacySettings,
+    pub(crate) description: Option<String>,
+    pub(crate) icon: Option<String>,
+    pub(crate) banner: Option<String>,
+    pub(crate) color: Option<String>,
+    /// `None` if the note doesn't declare members, memberships are left alone then
+    pub(crate) members: Option<Vec<String>>,
+    pub(crate) sync: SyncDirective,
+    /// configured key and the frontmatter key each field was read from
+    pub(crate) field_keys: Vec<(String, String)>,
 }
 
 impl MarkdownGroup {
@@ -145,51 +385,101 @@ impl MarkdownGroup {
         content: &str,
         cfg: &GroupFieldConfig,
     ) -> eyre::Result<Self> {
-        let Some(id_val) = frontmatter.get(&cfg.id) else {
-            return Err(eyre!("couldn't find id field `{}`", cfg.id));
-        };
-        let Some(id_str) = id_val.as_str() else {
+        let fields = FieldReader::new(path, frontmatter, cfg.case_insensitive);
+
+        let Some(id_str) = parse_id(&fields, &cfg.id)? else {
             return Err(eyre!("couldn't find id field `{}`", cfg.id));
         };
 
-        let uuid = cfg
-            .uuid
-            .as_ref()
-            .and_then(|uuid_field| frontmatter.get(uuid_field))
-            .and_then(|uuid_val| uuid_val.as_str())
-            .map(PluralKitUUID::from);
-
-        let name = cfg
-            .name
-            .as_ref()
-            .and_then(|name_field| frontmatter.get(name_field))
-            .and_then(|name_val| name_val.as_str())
-            .map(|str| str.to_owned())
-            .or_else(|| {
-                path.file_stem()
-                    .map(|stem| String::from(stem.to_string_lossy()))
-            });
-
-        let display_name = cfg
-            .display_name
-            .as_ref()
-            .and_then(|display_name_field| frontmatter.get(display_name_field))
-            .and_then(|display_name_val| display_name_val.as_str())
-            .map(|str| str.to_owned());
-
-        let private = cfg
-            .private
-            .as_ref()
-            .and_then(|private_field| frontmatter.get(private_field))
-            .and_then(|private_val| private_val.as_bool());
+        let uuid = fields
+            .string(cfg.uuid.as_ref())
+            .map(|uuid| PluralKitUUID::from(uuid.as_str()));
+
+        let name = fields.string(cfg.name.as_ref()).or_else(|| {
+            path.file_stem()
+                .map(|stem| String::from(stem.to_string_lossy()))
+        });
+
+        let display_name = fields.string(cfg.display_name.as_ref());
+
+        let privacy = PrivacySettings::parse(
+            &fields,
+            cfg.private.as_ref(),
+            cfg.privacy.as_ref(),
+            privacy::GROUP_KEYS,
+        )?;
+
+        let description = parse_description(
+            &cfg.description_source,
+            cfg.description_heading.as_ref(),
+            cfg.description.as_ref(),
+            &fields,
+            content,
+        )?;
+
+        let icon = parse_url(
+            cfg.icon.as_ref(),
+            fields.string(cfg.icon.as_ref()).as_deref(),
+        )?;
+        let banner = parse_url(
+            cfg.banner.as_ref(),
+            fields.string(cfg.banner.as_ref()).as_deref(),
+        )?;
+        let color = parse_color(
+            cfg.color.as_ref(),
+            fields.string(cfg.color.as_ref()).as_deref(),
+        )?;
+
+        let members = fields.list(cfg.members.as_ref()).or_else(|| {
+            cfg.members_heading
+                .as_ref()
+                .and_then(|heading| markdown::section(content, heading))
+                .map(markdown::list_links)
+        });
 
         Ok(Self {
-            id: PluralKitID::from(id_str),
+            id: PluralKitID::from(id_str.as_str()),
             uuid,
             name,
             display_name,
-            private,
+            privacy,
+            description,
+            icon,
+            banner,
+            color,
+            members,
+            sync: SyncDirective::parse(&fields, cfg.sync.as_ref(), sync_directive::GROUP_FIELDS)?,
             path: PathBuf::from(path),
+            field_keys: fields.into_used_keys(),
         })
     }
 }
+
+impl MarkdownGroup {
+    /// Check fields against pluralkit's length limits, truncating them where configured
+    pub(crate) fn enforce_limits(&mut self, cfg: &LimitConfig) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        if let Some(name) = &mut self.name {
+            violations.extend(limits::enforce("name", name, limits::NAME, cfg.name));
+        }
+        if let Some(display_name) = &mut self.display_name {
+            violations.extend(limits::enforce(
+                "display_name",
+                display_name,
+                limits::DISPLAY_NAME,
+                cfg.display_name,
+            ));
+        }
+        if let Some(description) = &mut self.description {
+            violations.extend(limits::enforce(
+                "description",
+                description,
+                limits::DESCRIPTION,
+                cfg.description,
+            ));
+        }
+
+        violations
+    }
+}
@@ -0,0 +1,644 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+
+use pkrs_fork::{
+    client::PkClient,
+    model::{Group, Member, PkId},
+};
+
+use crate::{
+    config::MembershipMode,
+    markdown_objects::{MarkdownGroup, MarkdownMember},
+    scan_result::ScanResult,
+};
+
+/// Member ids of every pluralkit group, keyed by group id
+pub(crate) type RemoteMemberships = HashMap<String, HashSet<String>>;
+
+pub(crate) async fn remote_memberships(
+    pk: &PkClient,
+    groups: &[Group],
+) -> eyre::Result<RemoteMemberships> {
+    let mut memberships = HashMap::new();
+    for group in groups {
+        let members = pk.get_group_members(&group.id).await?;
+        memberships.insert(
+            group.id.0.clone(),
+            members.into_iter().map(|member| member.id.0).collect(),
+        );
+    }
+
+    Ok(memberships)
+}
+
+/// Note name a `[[wikilink]]` points to, other references are returned trimmed
+pub(crate) fn link_target(reference: &str) -> &str {
+    let reference = reference.trim();
+    let Some(link) = reference
+        .strip_prefix("[[")
+        .and_then(|link| link.strip_suffix("]]"))
+    else {
+        return reference;
+    };
+
+    let link = link.split(['|', '#']).next().unwrap_or_default();
+    let link = link.rsplit('/').next().unwrap_or(link);
+    link.strip_suffix(".md").unwrap_or(link).trim()
+}
+
+pub(crate) fn note_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| String::from(stem.to_string_lossy()))
+        .unwrap_or_default()
+}
+
+/// Find the group `reference` points to by id, note name or name
+pub(crate) fn resolve_group<'a>(
+    reference: &str,
+    groups: &'a [MarkdownGroup],
+) -> Option<&'a MarkdownGroup> {
+    let target = link_target(reference);
+    groups
+        .iter()
+        .find(|group| group.id.as_ref() == target)
+        .or_else(|| groups.iter().find(|group| note_name(&group.path) == target))
+        .or_else(|| {
+            groups
+                .iter()
+                .find(|group| group.name.as_deref() == Some(target))
+        })
+        .or_else(|| {
+            groups
+                .iter()
+                .find(|group| note_name(&group.path).eq_ignore_ascii_case(target))
+        })
+}
+
+/// Groups declared on a member note, `None` if the note doesn't declare any
+pub(crate) fn declared_groups<'a>(
+    member: &MarkdownMember,
+    groups: &'a [MarkdownGroup],
+) -> Option<Vec<&'a MarkdownGroup>> {
+    let references = member.groups.as_ref()?;
+
+    let mut resolved = Vec::new();
+    for reference in references {
+        match resolve_group(reference, groups) {
+            Some(group) => resolved.push(group),
+            None => println!(
+                "WARN  {}: couldn't resolve group `{reference}`, skipping",
+                member.path.display()
+            ),
+        }
+    }
+
+    Some(resolved)
+}
+
+/// Find the member `reference` points to by id, note name, alias or name
+pub(crate) fn resolve_member<'a>(
+    reference: &str,
+    members: &'a [MarkdownMember],
+) -> Option<&'a MarkdownMember> {
+    let target = link_target(reference);
+    members
+        .iter()
+        .find(|member| member.id.as_ref().is_some_and(|id| id.as_ref() == target))
+        .or_else(|| {
+            members
+                .iter()
+                .find(|member| note_name(&member.path) == target)
+        })
+        .or_else(|| {
+            members
+                .iter()
+                .find(|member| member.aliases.iter().any(|alias| alias == target))
+        })
+        .or_else(|| {
+            members
+                .iter()
+                .find(|member| member.name.as_deref() == Some(target))
+        })
+        .or_else(|| {
+            members
+                .iter()
+                .find(|member| note_name(&member.path).eq_ignore_ascii_case(target))
+        })
+}
+
+/// Members declared on a group note, `None` if the note doesn't declare any
+pub(crate) fn declared_members<'a>(
+    group: &MarkdownGroup,
+    members: &'a [MarkdownMember],
+) -> Option<Vec<&'a MarkdownMember>> {
+    let references = group.members.as_ref()?;
+
+    let mut resolved = Vec::new();
+    for reference in references {
+        match resolve_member(reference, members) {
+            Some(member) => resolved.push(member),
+            None => println!(
+                "WARN  {}: couldn't resolve member `{reference}`, skipping",
+                group.path.display()
+            ),
+        }
+    }
+
+    Some(resolved)
+}
+
+/// Group a scanner edge points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum GroupTarget {
+    Existing(String),
+    /// No group note or pluralkit group has this name, it's created on sync
+    New(String),
+}
+
+impl fmt::Display for GroupTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Existing(id) => write!(f, "{id}"),
+            Self::New(name) => write!(f, "{name} (new)"),
+        }
+    }
+}
+
+/// Find the group a scanner edge names, by group note first and pluralkit group name second
+pub(crate) fn resolve_edge_group(
+    name: &str,
+    groups: &[MarkdownGroup],
+    remote_groups: &[Group],
+) -> GroupTarget {
+    resolve_group(name, groups)
+        .map(|group| String::from(group.id.as_ref()))
+        .or_else(|| {
+            remote_groups
+                .iter()
+                .find(|group| group.name == name)
+                .or_else(|| {
+                    remote_groups
+                        .iter()
+                        .find(|group| group.name.eq_ignore_ascii_case(name))
+                })
+                .map(|group| group.id.0.clone())
+        })
+        .map_or_else(
+            || GroupTarget::New(String::from(name)),
+            GroupTarget::Existing,
+        )
+}
+
+/// Groups a member that doesn't exist on pluralkit yet is added to once it's created
+pub(crate) fn new_member_groups(
+    member: &MarkdownMember,
+    files: &ScanResult,
+    remote_groups: &[Group],
+) -> Vec<GroupTarget> {
+    if !member.sync.includes("groups") {
+        return Vec::new();
+    }
+
+    let mut resolved: Vec<GroupTarget> = declared_groups(member, &files.groups)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|group| GroupTarget::Existing(String::from(group.id.as_ref())))
+        .collect();
+
+    let mut push = |target: GroupTarget| {
+        if !resolved.contains(&target) {
+            resolved.push(target);
+        }
+    };
+
+    for group in files
+        .groups
+        .iter()
+        .filter(|group| group.sync.includes("members"))
+    {
+        let rostered = group.members.iter().flatten().any(|reference| {
+            resolve_member(reference, &files.members)
+                .is_some_and(|found| std::ptr::eq(found, member))
+        });
+
+        if rostered {
+            push(GroupTarget::Existing(String::from(group.id.as_ref())));
+        }
+    }
+
+    for edge in files.edges.iter().filter(|edge| edge.member == member.path) {
+        push(resolve_edge_group(
+            &edge.group,
+            &files.groups,
+            remote_groups,
+        ));
+    }
+
+    resolved
+}
+
+/// Ids of member notes that don't sync their groups, group rosters leave them alone
+fn opted_out_members(members: &[MarkdownMember]) -> HashSet<&str> {
+    members
+        .iter()
+        .filter(|member| !member.sync.includes("groups"))
+        .filter_map(|member| member.id.as_ref().map(AsRef::as_ref))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct GroupChange {
+    pub(crate) add: BTreeSet<String>,
+    pub(crate) remove: BTreeSet<String>,
+    /// declared memberships pluralkit already has, they're never removed
+    pub(crate) keep: BTreeSet<String>,
+}
+
+/// Member ids to add to and remove from each group, keyed by group id
+///
+/// When sources disagree adding a member wins over removing it.
+#[derive(Debug, Default)]
+pub(crate) struct MembershipPlan {
+    pub(crate) groups: BTreeMap<String, GroupChange>,
+    /// Groups to create and the member ids they start with, keyed by name
+    pub(crate) new_groups: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl MembershipPlan {
+    /// Number of groups that have membership changes or are created
+    pub(crate) fn changed_groups(&self) -> usize {
+        self.groups
+            .values()
+            .filter(|change| !change.add.is_empty() || !change.remove.is_empty())
+            .count()
+            + self.new_groups.len()
+    }
+
+    /// Add a member to a group by target, new groups are created before members are added
+    pub(crate) fn add_to(&mut self, target: &GroupTarget, member_id: &str) {
+        match target {
+            GroupTarget::Existing(group_id) => self.add(group_id, member_id),
+            GroupTarget::New(name) => {
+                self.new_groups
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(String::from(member_id));
+            }
+        }
+    }
+
+    fn add(&mut self, group_id: &str, member_id: &str) {
+        let change = self.groups.entry(String::from(group_id)).or_default();
+        change.remove.remove(member_id);
+        change.add.insert(String::from(member_id));
+    }
+
+    /// Declared membership pluralkit already has, wins over removing it like adding does
+    fn keep(&mut self, group_id: &str, member_id: &str) {
+        let change = self.groups.entry(String::from(group_id)).or_default();
+        change.remove.remove(member_id);
+        change.keep.insert(String::from(member_id));
+    }
+
+    fn remove(&mut self, group_id: &str, member_id: &str) {
+        let change = self.groups.entry(String::from(group_id)).or_default();
+        if !change.add.contains(member_id) && !change.keep.contains(member_id) {
+            change.remove.insert(String::from(member_id));
+        }
+    }
+
+    /// Apply the groups declared on member notes
+    pub(crate) fn add_member_declarations(
+        &mut self,
+        members: &[MarkdownMember],
+        groups: &[MarkdownGroup],
+        remote: &RemoteMemberships,
+        mode: MembershipMode,
+    ) {
+        for member in members {
+            let Some(member_id) = &member.id else {
+                continue;
+            };
+            if !member.sync.includes("groups") {
+                continue;
+            }
+            let Some(declared) = declared_groups(member, groups) else {
+                continue;
+            };
+
+            let member_id = member_id.as_ref();
+            let declared: HashSet<&str> = declared.iter().map(|group| group.id.as_ref()).collect();
+
+            for group_id in &declared {
+                if remote
+                    .get(*group_id)
+                    .is_some_and(|members| members.contains(member_id))
+                {
+                    self.keep(group_id, member_id);
+                } else if mode.adds() {
+                    self.add(group_id, member_id);
+                }
+            }
+
+            if mode.removes() {
+                for (group_id, members) in remote {
+                    if members.contains(member_id) && !declared.contains(group_id.as_str()) {
+                        self.remove(group_id, member_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply the members declared on group notes
+    ///
+    /// Members that don't exist on pluralkit yet are added when they're created.
+    pub(crate) fn add_group_declarations(
+        &mut self,
+        groups: &[MarkdownGroup],
+        members: &[MarkdownMember],
+        remote: &RemoteMemberships,
+        mode: MembershipMode,
+    ) {
+        let opted_out = opted_out_members(members);
+        for group in groups {
+            if !group.sync.includes("members") {
+                continue;
+            }
+            let Some(declared) = declared_members(group, members) else {
+                continue;
+            };
+
+            let group_id = group.id.as_ref();
+            let declared: HashSet<&str> = declared
+                .iter()
+                .filter(|member| member.sync.includes("groups"))
+                .filter_map(|member| member.id.as_ref().map(AsRef::as_ref))
+                .collect();
+            let current = remote.get(group_id);
+
+            for member_id in &declared {
+                if current.is_some_and(|members| members.contains(*member_id)) {
+                    self.keep(group_id, member_id);
+                } else if mode.adds() {
+                    self.add(group_id, member_id);
+                }
+            }
+
+            if mode.removes() {
+                for member_id in current.into_iter().flatten() {
+                    if !declared.contains(member_id.as_str())
+                        && !opted_out.contains(member_id.as_str())
+                    {
+                        self.remove(group_id, member_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply the group edges derived by the scanner
+    ///
+    /// Folder groups are owned by the note layout regardless of the membership mode, tag groups
+    /// only when the mode removes memberships. Member notes without an edge to an owned group are
+    /// removed from it, members without a note are left alone.
+    pub(crate) fn add_edges(
+        &mut self,
+        files: &ScanResult,
+        remote_groups: &[Group],
+        remote: &RemoteMemberships,
+    ) {
+        let mut edges = HashSet::new();
+        for edge in &files.edges {
+            let Some(member_id) = files
+                .members
+                .iter()
+                .find(|member| member.path == edge.member)
+                .filter(|member| member.sync.includes("groups"))
+                .and_then(|member| member.id.as_ref())
+            else {
+                continue;
+            };
+
+            let member_id = member_id.as_ref();
+            let target = resolve_edge_group(&edge.group, &files.groups, remote_groups);
+            if let GroupTarget::Existing(group_id) = &target {
+                edges.insert((group_id.clone(), String::from(member_id)));
+                if remote
+                    .get(group_id)
+                    .is_some_and(|members| members.contains(member_id))
+                {
+                    self.keep(group_id, member_id);
+                    continue;
+                }
+            }
+            self.add_to(&target, member_id);
+        }
+
+        let noted: HashSet<&str> = files
+            .members
+            .iter()
+            .filter(|member| member.sync.includes("groups"))
+            .filter_map(|member| member.id.as_ref().map(AsRef::as_ref))
+            .collect();
+
+        for name in &files.edge_groups {
+            let GroupTarget::Existing(group_id) =
+                resolve_edge_group(name, &files.groups, remote_groups)
+            else {
+                continue;
+            };
+
+            for member_id in remote.get(&group_id).into_iter().flatten() {
+                if noted.contains(member_id.as_str())
+                    && !edges.contains(&(group_id.clone(), member_id.clone()))
+                {
+                    self.remove(&group_id, member_id);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn print(
+        &self,
+        remote_members: &HashMap<&str, &Member>,
+        remote_groups: &HashMap<&str, &Group>,
+    ) {
+        let member_name = |id: &str| {
+            remote_members
+                .get(id)
+                .map_or_else(String::new, |member| format!(" ({})", member.name))
+        };
+
+        for (name, members) in &self.new_groups {
+            println!("CREATE group {name}");
+            for member_id in members {
+                println!("  + {member_id}{}", member_name(member_id));
+            }
+        }
+
+        for (group_id, change) in &self.groups {
+            if change.add.is_empty() && change.remove.is_empty() {
+                continue;
+            }
+
+            let group_name = remote_groups
+                .get(group_id.as_str())
+                .map_or_else(String::new, |group| format!(" ({})", group.name));
+            println!("MEMBERS group {group_id}{group_name}");
+            for member_id in &change.add {
+                println!("  + {member_id}{}", member_name(member_id));
+            }
+            for member_id in &change.remove {
+                println!("  - {member_id}{}", member_name(member_id));
+            }
+        }
+    }
+
+    pub(crate) async fn apply(&self, pk: &PkClient) {
+        for (name, members) in &self.new_groups {
+            let group = Group {
+                name: name.clone(),
+                ..Default::default()
+            };
+            let created = match pk.create_group(&group).await {
+                Ok(created) => created,
+                Err(err) => {
+                    println!("ERROR group {name}: failed to create group: {err}");
+                    continue;
+                }
+            };
+            println!("OK     created group {} ({name})", created.id.0);
+
+            let members: Vec<PkId> = members.iter().cloned().map(PkId).collect();
+            if let Err(err) = pk.add_group_members(&created.id, &members).await {
+                println!("ERROR group {}: failed to add members: {err}", created.id.0);
+            }
+        }
+
+        for (group_id, change) in &self.groups {
+            let group = PkId(group_id.clone());
+
+            if !change.add.is_empty() {
+                let members: Vec<PkId> = change.add.iter().cloned().map(PkId).collect();
+                match pk.add_group_members(&group, &members).await {
+                    Ok(_) => println!(
+                        "OK     added {} member(s) to group {group_id}",
+                        members.len()
+                    ),
+                    Err(err) => println!("ERROR group {group_id}: failed to add members: {err}"),
+                }
+            }
+
+            if !change.remove.is_empty() {
+                let members: Vec<PkId> = change.remove.iter().cloned().map(PkId).collect();
+                match pk.remove_group_members(&group, &members).await {
+                    Ok(_) => println!(
+                        "OK     removed {} member(s) from group {group_id}",
+                        members.len()
+                    ),
+                    Err(err) => {
+                        println!("ERROR group {group_id}: failed to remove members: {err}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        markdown_objects::PluralKitID, privacy::PrivacySettings, scan_result::GroupEdge,
+        sync_directive::SyncDirective,
+    };
+
+    use super::*;
+
+    fn member(id: &str, note: &str, groups: Option<&[&str]>) -> MarkdownMember {
+        MarkdownMember {
+            id: Some(PluralKitID::from(id)),
+            groups: groups.map(|groups| groups.iter().copied().map(String::from).collect()),
+            ..MarkdownMember::named(note)
+        }
+    }
+
+    fn group(id: &str, note: &str, members: Option<&[&str]>) -> MarkdownGroup {
+        MarkdownGroup {
+            path: PathBuf::from(format!("groups/{note}.md")),
+            id: PluralKitID::from(id),
+            uuid: None,
+            name: Some(String::from(note)),
+            display_name: None,
+            privacy: PrivacySettings::default(),
+            description: None,
+            icon: None,
+            banner: None,
+            color: None,
+            members: members.map(|members| members.iter().copied().map(String::from).collect()),
+            sync: SyncDirective::default(),
+            field_keys: Vec::new(),
+        }
+    }
+
+    fn remote(memberships: &[(&str, &[&str])]) -> RemoteMemberships {
+        memberships
+            .iter()
+            .map(|(group_id, members)| {
+                (
+                    String::from(*group_id),
+                    members.iter().copied().map(String::from).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn declared_memberships_are_kept_across_sources() {
+        // A's note only lists G1, but G2's roster and A's folder for G3 declare A too
+        let files = ScanResult {
+            members: vec![member("aaaaa", "A", Some(&["G1"]))],
+            groups: vec![
+                group("ggg01", "G1", None),
+                group("ggg02", "G2", Some(&["[[A]]"])),
+            ],
+            edges: vec![GroupEdge {
+                member: PathBuf::from("members/A.md"),
+                group: String::from("G3"),
+            }],
+            edge_groups: BTreeSet::from([String::from("G3")]),
+        };
+        let remote_groups = vec![Group {
+            id: PkId(String::from("ggg03")),
+            name: String::from("G3"),
+            ..Default::default()
+        }];
+        let remote = remote(&[
+            ("ggg01", &[]),
+            ("ggg02", &["aaaaa", "bbbbb"]),
+            ("ggg03", &["aaaaa"]),
+        ]);
+
+        let mut plan = MembershipPlan::default();
+        let mode = MembershipMode::Overwrite;
+        plan.add_member_declarations(&files.members, &files.groups, &remote, mode);
+        plan.add_group_declarations(&files.groups, &files.members, &remote, mode);
+        plan.add_edges(&files, &remote_groups, &remote);
+
+        assert_eq!(
+            plan.groups["ggg01"].add,
+            BTreeSet::from([String::from("aaaaa")])
+        );
+        assert_eq!(
+            plan.groups["ggg02"].remove,
+            BTreeSet::from([String::from("bbbbb")])
+        );
+        assert!(plan.groups["ggg03"].remove.is_empty());
+        assert_eq!(plan.changed_groups(), 2);
+    }
+}
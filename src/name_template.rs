@@ -0,0 +1,119 @@
+use eyre::eyre;
+
+use crate::{config::NameConfig, markdown_objects::MarkdownMember};
+
+/// Template used when `display_name_pronouns` is set without a template of its own
+const PRONOUNS_TEMPLATE: &str = "{display_name} ({pronouns})";
+
+/// Display name template to render, if any
+pub(crate) fn template(cfg: &NameConfig) -> Option<&str> {
+    cfg.display_name_template
+        .as_deref()
+        .or(cfg.display_name_pronouns.then_some(PRONOUNS_TEMPLATE))
+}
+
+/// Value of the mapped field `key`, `{display_name}` falls back to the name like pluralkit does
+fn field(member: &MarkdownMember, key: &str) -> eyre::Result<Option<String>> {
+    let value = match key {
+        "id" => member.id.as_ref().map(|id| String::from(id.as_ref())),
+        "uuid" => member.uuid.as_ref().map(|uuid| String::from(uuid.as_ref())),
+        "name" => member.name.clone(),
+        "display_name" => member.display_name.clone().or_else(|| member.name.clone()),
+        "pronouns" => (!member.pronouns.is_empty()).then(|| member.pronouns.join(", ")),
+        "color" => member.color.as_ref().map(|color| format!("#{color}")),
+        "birthday" => member.birthday.map(|birthday| birthday.to_string()),
+        _ => return Err(eyre!("unknown field `{{{key}}}` in display name template")),
+    };
+
+    Ok(value.filter(|value| !value.is_empty()))
+}
+
+/// Render `template` with the fields of `member`, `{{` and `}}` are literal braces
+///
+/// Returns `None` if a referenced field has no value, the display name is left as is then.
+pub(crate) fn render(template: &str, member: &MarkdownMember) -> eyre::Result<Option<String>> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(idx) = rest.find(['{', '}']) {
+        out.push_str(&rest[..idx]);
+        let tail = &rest[idx..];
+
+        if let Some(escaped) = tail.strip_prefix("{{").or_else(|| tail.strip_prefix("}}")) {
+            out.push_str(&tail[..1]);
+            rest = escaped;
+            continue;
+        }
+
+        let Some(end) = tail.strip_prefix('{').and_then(|field| field.find('}')) else {
+            return Err(eyre!(
+                "unmatched brace in display name template `{template}`"
+            ));
+        };
+
+        let Some(value) = field(member, &tail[1..=end])? else {
+            return Ok(None);
+        };
+        out.push_str(&value);
+        rest = &tail[end + 2..];
+    }
+    out.push_str(rest);
+
+    Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alex() -> MarkdownMember {
+        MarkdownMember {
+            display_name: Some(String::from("Lex")),
+            pronouns: vec![String::from("she/her"), String::from("they/them")],
+            ..MarkdownMember::named("Alex")
+        }
+    }
+
+    #[test]
+    fn renders_fields() {
+        assert_eq!(
+            render("{display_name} ({pronouns})", &alex()).unwrap(),
+            Some(String::from("Lex (she/her, they/them)"))
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(
+            render("{{{name}}} }}{{", &alex()).unwrap(),
+            Some(String::from("{Alex} }{"))
+        );
+    }
+
+    #[test]
+    fn unmatched_braces_error() {
+        assert!(render("{name", &alex()).is_err());
+        assert!(render("name}", &alex()).is_err());
+    }
+
+    #[test]
+    fn unknown_field_errors() {
+        assert!(render("{nickname}", &alex()).is_err());
+    }
+
+    #[test]
+    fn empty_field_leaves_display_name_alone() {
+        assert_eq!(render("{name} {color}", &alex()).unwrap(), None);
+        let mut member = alex();
+        member.pronouns.clear();
+        assert_eq!(render("{name} ({pronouns})", &member).unwrap(), None);
+    }
+
+    #[test]
+    fn display_name_falls_back_to_name() {
+        let member = MarkdownMember::named("Alex");
+        assert_eq!(
+            render("{display_name}", &member).unwrap(),
+            Some(String::from("Alex"))
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use eyre::eyre;
+use frontmatter_gen::Value;
+
+use crate::{
+    config::FieldKeys,
+    fields::{self, FieldReader},
+};
+
+/// Privacy settings of a pluralkit member, named like the frontmatter keys
+pub(crate) const MEMBER_KEYS: &[&str] = &[
+    "visibility",
+    "name",
+    "description",
+    "birthday",
+    "pronouns",
+    "avatar",
+    "metadata",
+    "proxy",
+];
+
+/// Privacy settings of a pluralkit group, named like the frontmatter keys
+pub(crate) const GROUP_KEYS: &[&str] = &[
+    "visibility",
+    "name",
+    "description",
+    "icon",
+    "list",
+    "metadata",
+    "banner",
+];
+
+fn parse_private(field: &str, key: &str, value: &Value) -> eyre::Result<bool> {
+    match value
+        .as_str()
+        .map(|value| value.trim().to_ascii_lowercase())
+    {
+        Some(value) if value == "private" => Ok(true),
+        Some(value) if value == "public" => Ok(false),
+        _ => fields::to_bool(value).ok_or_else(|| {
+            eyre!("`{field}.{key}` must be `public`, `private` or yes/no, got `{value:?}`")
+        }),
+    }
+}
+
+/// Whether each privacy setting is private, settings that aren't in markdown are left alone
+#[derive(Debug, Default)]
+pub(crate) struct PrivacySettings {
+    settings: BTreeMap<&'static str, bool>,
+    /// the visibility was set by the `privacy` field rather than the `private` bool
+    visibility_from_privacy: bool,
+}
+
+impl PrivacySettings {
+    /// Read the `privacy` field, either a bool for every setting or a map of single settings
+    ///
+    /// The `private` bool sets the visibility if the `privacy` field doesn't.
+    pub(crate) fn parse(
+        fields: &FieldReader,
+        private_field: Option<&FieldKeys>,
+        privacy_field: Option<&FieldKeys>,
+        keys: &'static [&'static str],
+    ) -> eyre::Result<Self> {
+        let mut settings = BTreeMap::new();
+
+        if let Some((field, value)) = fields.value(privacy_field) {
+            if let Some(private) = fields::to_bool(value) {
+                settings.extend(keys.iter().map(|key| (*key, private)));
+            } else if let Some(map) = value.as_object() {
+                for (key, value) in map.iter() {
+                    let Some(key) = keys.iter().find(|known| *known == key) else {
+                        return Err(eyre!(
+                            "unknown privacy setting `{field}.{key}`, valid settings are {}",
+                            keys.join(", ")
+                        ));
+                    };
+                    settings.insert(*key, parse_private(&field, key, value)?);
+                }
+            } else {
+                return Err(eyre!(
+                    "`{field}` must be a bool or a map of privacy settings, got `{value:?}`"
+                ));
+            }
+        }
+
+        let visibility_from_privacy = settings.contains_key("visibility");
+        if let Some(private) = fields.bool(private_field) {
+            settings.entry("visibility").or_insert(private);
+        }
+
+        Ok(Self {
+            settings,
+            visibility_from_privacy,
+        })
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<bool> {
+        self.settings.get(key).copied()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    /// Whether writing the visibility to the `private` bool takes effect, the `privacy` field
+    /// wins over it when it sets the visibility
+    pub(crate) fn pulls_visibility(&self) -> bool {
+        !self.visibility_from_privacy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use frontmatter_gen::Frontmatter;
+
+    use super::*;
+
+    fn frontmatter(entries: Vec<(&str, Value)>) -> Frontmatter {
+        let mut frontmatter = Frontmatter::new();
+        for (key, value) in entries {
+            frontmatter.insert(String::from(key), value);
+        }
+        frontmatter
+    }
+
+    fn string(value: &str) -> Value {
+        Value::String(String::from(value))
+    }
+
+    fn parse(frontmatter: &Frontmatter) -> eyre::Result<PrivacySettings> {
+        let fields = FieldReader::new(Path::new("Alex.md"), frontmatter, false);
+        PrivacySettings::parse(
+            &fields,
+            Some(&FieldKeys::from("private")),
+            Some(&FieldKeys::from("privacy")),
+            MEMBER_KEYS,
+        )
+    }
+
+    #[test]
+    fn bool_sets_every_setting() {
+        let settings = parse(&frontmatter(vec![("privacy", string("yes"))])).unwrap();
+        assert!(
+            MEMBER_KEYS
+                .iter()
+                .all(|key| settings.get(key) == Some(true))
+        );
+        assert!(!settings.pulls_visibility());
+    }
+
+    #[test]
+    fn map_sets_single_settings() {
+        let privacy = frontmatter(vec![
+            ("name", string("Private")),
+            ("description", string("public")),
+            ("birthday", Value::Boolean(true)),
+        ]);
+        let settings = parse(&frontmatter(vec![
+            ("privacy", Value::Object(Box::new(privacy))),
+            ("private", Value::Boolean(true)),
+        ]))
+        .unwrap();
+        assert_eq!(settings.get("name"), Some(true));
+        assert_eq!(settings.get("description"), Some(false));
+        assert_eq!(settings.get("birthday"), Some(true));
+        assert_eq!(settings.get("pronouns"), None);
+        // the map doesn't set the visibility, so the `private` bool does
+        assert_eq!(settings.get("visibility"), Some(true));
+        assert!(settings.pulls_visibility());
+    }
+
+    #[test]
+    fn privacy_visibility_wins_over_private() {
+        let privacy = frontmatter(vec![("visibility", string("public"))]);
+        let settings = parse(&frontmatter(vec![
+            ("privacy", Value::Object(Box::new(privacy))),
+            ("private", Value::Boolean(true)),
+        ]))
+        .unwrap();
+        assert_eq!(settings.get("visibility"), Some(false));
+        assert!(!settings.pulls_visibility());
+    }
+
+    #[test]
+    fn invalid_settings_error() {
+        let unknown = frontmatter(vec![("icon", string("private"))]);
+        assert!(
+            parse(&frontmatter(vec![(
+                "privacy",
+                Value::Object(Box::new(unknown))
+            )]))
+            .is_err()
+        );
+
+        let invalid = frontmatter(vec![("name", string("secret"))]);
+        assert!(
+            parse(&frontmatter(vec![(
+                "privacy",
+                Value::Object(Box::new(invalid))
+            )]))
+            .is_err()
+        );
+
+        assert!(parse(&frontmatter(vec![("privacy", string("secret"))])).is_err());
+    }
+}
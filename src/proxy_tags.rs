@@ -0,0 +1,269 @@
+use std::fmt;
+
+use eyre::eyre;
+use pkrs_fork::model::ProxyTag;
+
+use crate::markdown_objects::MarkdownMember;
+
+pub(crate) const DEFAULT_PLACEHOLDER: &str = "text";
+
+/// Proxy tag written as the text around a placeholder, like `A:text` or `[text]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MarkdownProxyTag {
+    pub(crate) prefix: Option<String>,
+    pub(crate) suffix: Option<String>,
+}
+
+impl MarkdownProxyTag {
+    pub(crate) fn parse(tag: &str, placeholder: &str) -> eyre::Result<Self> {
+        if placeholder.is_empty() {
+            return Err(eyre!("the proxy tag placeholder can't be empty"));
+        }
+
+        // split at the last placeholder so a prefix can contain it, like `context:text`
+        let Some((prefix, suffix)) = tag.rsplit_once(placeholder) else {
+            return Err(eyre!(
+                "proxy tag `{tag}` doesn't contain the placeholder `{placeholder}`"
+            ));
+        };
+
+        if prefix.is_empty() && suffix.is_empty() {
+            return Err(eyre!(
+                "proxy tag `{tag}` needs a prefix or suffix around `{placeholder}`"
+            ));
+        }
+
+        Ok(Self {
+            prefix: (!prefix.is_empty()).then(|| String::from(prefix)),
+            suffix: (!suffix.is_empty()).then(|| String::from(suffix)),
+        })
+    }
+
+    pub(crate) fn format(&self, placeholder: &str) -> String {
+        format!(
+            "{}{placeholder}{}",
+            self.prefix.as_deref().unwrap_or_default(),
+            self.suffix.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Prefix and suffix without the placeholder, the part pluralkit limits in length
+    pub(crate) fn tag_text(&self) -> String {
+        self.format("")
+    }
+}
+
+impl From<&ProxyTag> for MarkdownProxyTag {
+    fn from(tag: &ProxyTag) -> Self {
+        Self {
+            prefix: tag.prefix.clone().filter(|prefix| !prefix.is_empty()),
+            suffix: tag.suffix.clone().filter(|suffix| !suffix.is_empty()),
+        }
+    }
+}
+
+impl From<&MarkdownProxyTag> for ProxyTag {
+    fn from(tag: &MarkdownProxyTag) -> Self {
+        Self {
+            prefix: tag.prefix.clone(),
+            suffix: tag.suffix.clone(),
+        }
+    }
+}
+
+/// Two members whose proxy tags can match the same message
+#[derive(Debug)]
+pub(crate) enum Collision<'a> {
+    Duplicate {
+        placeholder: &'a str,
+        tag: &'a MarkdownProxyTag,
+        first: &'a MarkdownMember,
+        second: &'a MarkdownMember,
+    },
+    /// every message proxied by `shadowed` also matches `tag`
+    Shadowed {
+        placeholder: &'a str,
+        tag: &'a MarkdownProxyTag,
+        member: &'a MarkdownMember,
+        shadowed_tag: &'a MarkdownProxyTag,
+        shadowed: &'a MarkdownMember,
+    },
+}
+
+impl fmt::Display for Collision<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |member: &MarkdownMember| {
+            member
+                .name
+                .clone()
+                .unwrap_or_else(|| member.path.display().to_string())
+        };
+
+        match self {
+            Self::Duplicate {
+                placeholder,
+                tag,
+                first,
+                second,
+            } => write!(
+                f,
+                "proxy tag `{}` is used by both {} and {}",
+                tag.format(placeholder),
+                name(first),
+                name(second)
+            ),
+            Self::Shadowed {
+                placeholder,
+                tag,
+                member,
+                shadowed_tag,
+                shadowed,
+            } => write!(
+                f,
+                "proxy tag `{}` of {} overlaps `{}` of {}",
+                tag.format(placeholder),
+                name(member),
+                shadowed_tag.format(placeholder),
+                name(shadowed)
+            ),
+        }
+    }
+}
+
+/// Whether every message matching `other` also matches `tag`
+fn covers(tag: &MarkdownProxyTag, other: &MarkdownProxyTag) -> bool {
+    let prefix = tag.prefix.as_deref().unwrap_or_default();
+    let suffix = tag.suffix.as_deref().unwrap_or_default();
+    other
+        .prefix
+        .as_deref()
+        .unwrap_or_default()
+        .starts_with(prefix)
+        && other
+            .suffix
+            .as_deref()
+            .unwrap_or_default()
+            .ends_with(suffix)
+}
+
+/// Proxy tags shared or overlapping between different members, shown with `placeholder`
+///
+/// Members whose proxy tags aren't synced can't cause a collision and are left out.
+pub(crate) fn collisions<'a>(
+    members: &'a [MarkdownMember],
+    placeholder: &'a str,
+) -> Vec<Collision<'a>> {
+    let tags: Vec<(&MarkdownMember, &MarkdownProxyTag)> = members
+        .iter()
+        .filter(|member| member.sync.includes("proxy_tags"))
+        .flat_map(|member| member.proxy_tags.iter().map(move |tag| (member, tag)))
+        .collect();
+
+    let mut collisions = Vec::new();
+    for (idx, (member, tag)) in tags.iter().enumerate() {
+        for (other_member, other_tag) in &tags[idx + 1..] {
+            if std::ptr::eq(*member, *other_member) {
+                continue;
+            }
+
+            if tag == other_tag {
+                collisions.push(Collision::Duplicate {
+                    placeholder,
+                    tag,
+                    first: member,
+                    second: other_member,
+                });
+            } else if covers(tag, other_tag) {
+                collisions.push(Collision::Shadowed {
+                    placeholder,
+                    tag,
+                    member,
+                    shadowed_tag: other_tag,
+                    shadowed: other_member,
+                });
+            } else if covers(other_tag, tag) {
+                collisions.push(Collision::Shadowed {
+                    placeholder,
+                    tag: other_tag,
+                    member: other_member,
+                    shadowed_tag: tag,
+                    shadowed: member,
+                });
+            }
+        }
+    }
+
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync_directive::SyncDirective;
+
+    use super::*;
+
+    fn member(name: &str, tags: &[&str]) -> MarkdownMember {
+        MarkdownMember {
+            proxy_tags: tags
+                .iter()
+                .map(|tag| MarkdownProxyTag::parse(tag, "msg").unwrap())
+                .collect(),
+            ..MarkdownMember::named(name)
+        }
+    }
+
+    #[test]
+    fn parse_splits_at_last_placeholder() {
+        let tag = MarkdownProxyTag::parse("context:text", DEFAULT_PLACEHOLDER).unwrap();
+        assert_eq!(tag.prefix.as_deref(), Some("context:"));
+        assert_eq!(tag.suffix, None);
+
+        let tag = MarkdownProxyTag::parse("[text]", DEFAULT_PLACEHOLDER).unwrap();
+        assert_eq!(tag.prefix.as_deref(), Some("["));
+        assert_eq!(tag.suffix.as_deref(), Some("]"));
+
+        assert!(MarkdownProxyTag::parse("text", DEFAULT_PLACEHOLDER).is_err());
+        assert!(MarkdownProxyTag::parse("a:", DEFAULT_PLACEHOLDER).is_err());
+    }
+
+    #[test]
+    fn exact_duplicates_collide() {
+        let members = [member("A", &["a:msg"]), member("B", &["a:msg"])];
+        let collisions = collisions(&members, "msg");
+        assert_eq!(collisions.len(), 1);
+        assert!(matches!(collisions[0], Collision::Duplicate { .. }));
+        assert_eq!(
+            collisions[0].to_string(),
+            "proxy tag `a:msg` is used by both A and B"
+        );
+    }
+
+    #[test]
+    fn prefix_shadows_only_when_it_starts_the_other() {
+        // `al:hi` doesn't start with `a:`, so these two never match the same message
+        let members = [member("A", &["a:msg"]), member("B", &["al:msg"])];
+        assert!(collisions(&members, "msg").is_empty());
+
+        let members = [member("A", &["amsg"]), member("B", &["al:msg"])];
+        let collisions = collisions(&members, "msg");
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(
+            collisions[0].to_string(),
+            "proxy tag `amsg` of A overlaps `al:msg` of B"
+        );
+    }
+
+    #[test]
+    fn members_not_syncing_proxy_tags_dont_collide() {
+        let mut skipped = member("B", &["a:msg"]);
+        skipped.sync = SyncDirective::Skip;
+        let members = [member("A", &["a:msg"]), skipped];
+        assert!(collisions(&members, "msg").is_empty());
+    }
+
+    #[test]
+    fn tags_of_the_same_member_dont_collide() {
+        let members = [member("A", &["a:msg", "al:msg"]), member("B", &["b:msg"])];
+        assert!(collisions(&members, "msg").is_empty());
+    }
+}
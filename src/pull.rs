@@ -0,0 +1,495 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use pkrs_fork::{
+    client::PkClient,
+    model::{Group, Member, PkId, Privacy},
+};
+
+use crate::{
+    config::{
+        Config, DescriptionSource, FieldKeys, FieldOwners, GroupFieldConfig, MemberFieldConfig,
+        NameConfig,
+    },
+    diff::{FieldChange, GroupDiff, MemberDiff, Resolution},
+    frontmatter::{FrontmatterEditor, YamlValue},
+    get_files,
+    markdown_objects::{MarkdownGroup, MarkdownMember},
+    name_template,
+    proxy_tags::MarkdownProxyTag,
+    snapshot::FieldSnapshot,
+};
+
+/// Name `pull` knows a synced field by, of the privacy settings only the visibility is pulled
+pub(crate) fn pull_field(field: &str) -> &str {
+    match field {
+        "visibility" => "privacy",
+        field => field,
+    }
+}
+
+/// Fields by `pull` name whose note value already reads as the PluralKit value
+///
+/// Compares the coerced note values, so notations like `Private: yes` aren't rewritten.
+fn unchanged_fields(snapshot: &FieldSnapshot, changes: &[FieldChange]) -> HashSet<String> {
+    snapshot
+        .keys()
+        .filter(|field| !changes.iter().any(|change| change.field == field.as_str()))
+        .map(|field| String::from(pull_field(field)))
+        .collect()
+}
+
+fn is_private(privacy: Option<Privacy>) -> Option<bool> {
+    privacy.map(|privacy| matches!(privacy, Privacy::Private))
+}
+
+/// Description frontmatter field, only set when the description is read from frontmatter
+fn description_field<'a>(
+    source: &DescriptionSource,
+    field: &'a Option<FieldKeys>,
+) -> &'a Option<FieldKeys> {
+    match source {
+        DescriptionSource::Field => field,
+        _ => &None,
+    }
+}
+
+/// Display name frontmatter field, not set when the display name sent to pluralkit is templated
+fn display_name_field<'a>(
+    name_cfg: &NameConfig,
+    field: &'a Option<FieldKeys>,
+) -> &'a Option<FieldKeys> {
+    match name_template::template(name_cfg) {
+        Some(_) => &None,
+        None => field,
+    }
+}
+
+/// Whether `pull` has a frontmatter field in `member`'s note to write `field` by sync plan name
+pub(crate) fn member_field_writable(
+    cfg: &MemberFieldConfig,
+    name_cfg: &NameConfig,
+    member: &MarkdownMember,
+    field: &str,
+) -> bool {
+    let key = match pull_field(field) {
+        "name" => &cfg.name,
+        "display_name" => display_name_field(name_cfg, &cfg.display_name),
+        "description" => description_field(&cfg.description_source, &cfg.description),
+        "pronouns" => &cfg.pronouns,
+        "proxy_tags" => &cfg.proxy_tags,
+        "avatar_url" => &cfg.avatar_url,
+        "webhook_avatar_url" => &cfg.webhook_avatar_url,
+        "banner" => &cfg.banner,
+        "color" => &cfg.color,
+        "birthday" => &cfg.birthday,
+        "keep_proxy" => &cfg.keep_proxy,
+        "tts" => &cfg.tts,
+        "autoproxy_enabled" => &cfg.autoproxy_enabled,
+        "privacy" if member.privacy.pulls_visibility() => &cfg.private,
+        _ => &None,
+    };
+    key.is_some()
+}
+
+/// Whether `pull` has a frontmatter field in `group`'s note to write `field` by sync plan name
+pub(crate) fn group_field_writable(
+    cfg: &GroupFieldConfig,
+    group: &MarkdownGroup,
+    field: &str,
+) -> bool {
+    let key = match pull_field(field) {
+        "name" => &cfg.name,
+        "display_name" => &cfg.display_name,
+        "description" => description_field(&cfg.description_source, &cfg.description),
+        "icon" => &cfg.icon,
+        "banner" => &cfg.banner,
+        "color" => &cfg.color,
+        "privacy" if group.privacy.pulls_visibility() => &cfg.private,
+        _ => &None,
+    };
+    key.is_some()
+}
+
+/// Frontmatter values for every configured member field PluralKit has a value for
+///
+/// Only fields `include` accepts by sync plan name are returned, the id is always included.
+pub(crate) fn member_values(
+    cfg: &MemberFieldConfig,
+    name_cfg: &NameConfig,
+    include: impl Fn(&str) -> bool,
+    remote: &Member,
+) -> Vec<(FieldKeys, YamlValue)> {
+    let mut values = vec![(cfg.id.clone(), YamlValue::String(remote.id.0.clone()))];
+
+    let pronouns = remote
+        .pronouns
+        .as_ref()
+        .map(|pronouns| pronouns.split(", ").map(String::from).collect());
+    let proxy_tags = (!remote.proxy_tags.is_empty()).then(|| {
+        remote
+            .proxy_tags
+            .iter()
+            .map(|tag| MarkdownProxyTag::from(tag).format(&cfg.proxy_tag_placeholder))
+            .collect()
+    });
+
+    let fields = [
+        (
+            "uuid",
+            &cfg.uuid,
+            Some(YamlValue::String(remote.uuid.to_string())),
+        ),
+        (
+            "name",
+            &cfg.name,
+            Some(YamlValue::String(remote.name.clone())),
+        ),
+        (
+            "display_name",
+            display_name_field(name_cfg, &cfg.display_name),
+            remote.display_name.clone().map(YamlValue::String),
+        ),
+        (
+            "description",
+            description_field(&cfg.description_source, &cfg.description),
+            remote.description.clone().map(YamlValue::String),
+        ),
+        ("pronouns", &cfg.pronouns, pronouns.map(YamlValue::List)),
+        (
+            "proxy_tags",
+            &cfg.proxy_tags,
+            proxy_tags.map(YamlValue::List),
+        ),
+        (
+            "avatar_url",
+            &cfg.avatar_url,
+            remote.avatar_url.clone().map(YamlValue::String),
+        ),
+        (
+            "webhook_avatar_url",
+            &cfg.webhook_avatar_url,
+            remote.webhook_avatar_url.clone().map(YamlValue::String),
+        ),
+        (
+            "banner",
+            &cfg.banner,
+            remote.banner.clone().map(YamlValue::String),
+        ),
+        (
+            "color",
+            &cfg.color,
+            remote.color.clone().map(YamlValue::String),
+        ),
+        (
+            "birthday",
+            &cfg.birthday,
+            remote
+                .birthday
+                .map(|birthday| YamlValue::String(birthday.to_string())),
+        ),
+        (
+            "keep_proxy",
+            &cfg.keep_proxy,
+            Some(YamlValue::Bool(remote.keep_proxy)),
+        ),
+        ("tts", &cfg.tts, Some(YamlValue::Bool(remote.tts))),
+        (
+            "autoproxy_enabled",
+            &cfg.autoproxy_enabled,
+            remote.autoproxy_enabled.map(YamlValue::Bool),
+        ),
+        (
+            "privacy",
+            &cfg.private,
+            is_private(
+                remote
+                    .privacy
+                    .as_ref()
+                    .and_then(|privacy| privacy.visibility),
+            )
+            .map(YamlValue::Bool),
+        ),
+    ];
+
+    for (name, field, value) in fields {
+        if let (Some(field), Some(value)) = (field, value)
+            && include(name)
+        {
+            values.push((field.clone(), value));
+        }
+    }
+
+    values
+}
+
+/// Frontmatter values for every configured group field PluralKit has a value for
+///
+/// Only fields `include` accepts by sync plan name are returned, the id is always included.
+pub(crate) fn group_values(
+    cfg: &GroupFieldConfig,
+    include: impl Fn(&str) -> bool,
+    remote: &Group,
+) -> Vec<(FieldKeys, YamlValue)> {
+    let mut values = vec![(cfg.id.clone(), YamlValue::String(remote.id.0.clone()))];
+
+    let fields = [
+        (
+            "uuid",
+            &cfg.uuid,
+            Some(YamlValue::String(remote.uuid.to_string())),
+        ),
+        (
+            "name",
+            &cfg.name,
+            Some(YamlValue::String(remote.name.clone())),
+        ),
+        (
+            "display_name",
+            &cfg.display_name,
+            remote.display_name.clone().map(YamlValue::String),
+        ),
+        (
+            "description",
+            description_field(&cfg.description_source, &cfg.description),
+            remote.description.clone().map(YamlValue::String),
+        ),
+        (
+            "icon",
+            &cfg.icon,
+            remote.icon.clone().map(YamlValue::String),
+        ),
+        (
+            "banner",
+            &cfg.banner,
+            remote.banner.clone().map(YamlValue::String),
+        ),
+        (
+            "color",
+            &cfg.color,
+            remote.color.clone().map(YamlValue::String),
+        ),
+        (
+            "privacy",
+            &cfg.private,
+            is_private(
+                remote
+                    .privacy
+                    .as_ref()
+                    .and_then(|privacy| privacy.visibility),
+            )
+            .map(YamlValue::Bool),
+        ),
+    ];
+
+    for (name, field, value) in fields {
+        if let (Some(field), Some(value)) = (field, value)
+            && include(name)
+        {
+            values.push((field.clone(), value));
+        }
+    }
+
+    values
+}
+
+/// Write `values` to the note at `path`, returns whether it had any changes
+pub(crate) fn pull_note(
+    kind: &str,
+    id: &str,
+    path: &Path,
+    values: &[(FieldKeys, YamlValue)],
+    case_insensitive: bool,
+    quiet: bool,
+    execute: bool,
+) -> eyre::Result<bool> {
+    let content = std::fs::read_to_string(path)?;
+    let mut editor = FrontmatterEditor::parse(&content)?;
+
+    let mut changed = Vec::new();
+    for (keys, value) in values {
+        let key = editor.resolve_key(keys, case_insensitive);
+        match editor.set_field(&key, value, case_insensitive) {
+            Ok(true) => changed.push((key, value)),
+            Ok(false) => {}
+            Err(err) => println!("ERROR {}: failed to write `{key}`: {err}", path.display()),
+        }
+    }
+
+    if changed.is_empty() {
+        if !quiet {
+            println!("OK     {kind} {id} ({})", path.display());
+        }
+        return Ok(false);
+    }
+
+    println!("UPDATE {kind} {id} ({})", path.display());
+    for (key, value) in changed {
+        println!("  {key}: {value}");
+    }
+
+    if execute {
+        std::fs::write(path, editor.render())?;
+    }
+
+    Ok(true)
+}
+
+pub(crate) async fn run(
+    pk: &PkClient,
+    conf: &Config,
+    quiet: bool,
+    execute: bool,
+) -> eyre::Result<()> {
+    let system = pk.get_system(&PkId("@me".into())).await?;
+    println!(
+        "Pulling System: {} ...",
+        system.name.as_deref().unwrap_or(&system.id.0)
+    );
+
+    let files = get_files(conf)?;
+
+    let remote_members = pk.get_system_members(&system.id).await?;
+    let members_by_id: HashMap<&str, &Member> = remote_members
+        .iter()
+        .map(|member| (member.id.0.as_str(), member))
+        .collect();
+    let members_by_uuid: HashMap<String, &Member> = remote_members
+        .iter()
+        .map(|member| (member.uuid.to_string(), member))
+        .collect();
+
+    let remote_groups = pk.get_system_groups(&system.id).await?;
+    let groups_by_id: HashMap<&str, &Group> = remote_groups
+        .iter()
+        .map(|group| (group.id.0.as_str(), group))
+        .collect();
+    let groups_by_uuid: HashMap<String, &Group> = remote_groups
+        .iter()
+        .map(|group| (group.uuid.to_string(), group))
+        .collect();
+
+    let mut updated = 0;
+    for member in &files.members {
+        let remote = member
+            .id
+            .as_ref()
+            .and_then(|id| members_by_id.get(id.as_ref()))
+            .or_else(|| {
+                member
+                    .uuid
+                    .as_ref()
+                    .and_then(|uuid| members_by_uuid.get(uuid.as_ref()))
+            });
+
+        if member.sync.is_skipped() {
+            if !quiet {
+                println!("SKIP   member ({}): sync is off", member.path.display());
+            }
+            continue;
+        }
+
+        let Some(remote) = remote else {
+            println!(
+                "WARN  {}: no matching member on PluralKit, skipping",
+                member.path.display()
+            );
+            continue;
+        };
+
+        let diff = MemberDiff::new(
+            member,
+            remote,
+            &FieldOwners::default(),
+            &conf.fields.member.proxy_tag_placeholder,
+            None,
+            &mut |_| Resolution::Skip,
+        );
+        let unchanged = unchanged_fields(&diff.snapshot, &diff.changes);
+        let values = member_values(
+            &conf.fields.member,
+            &conf.name,
+            |field| {
+                conf.ownership.member.pulls(field)
+                    && (field == "uuid" || member.sync.includes(field))
+                    && (field != "privacy" || member.privacy.pulls_visibility())
+                    && !unchanged.contains(field)
+            },
+            remote,
+        );
+        match pull_note(
+            "member",
+            &remote.id.0,
+            &member.path,
+            &values,
+            conf.fields.member.case_insensitive,
+            quiet,
+            execute,
+        ) {
+            Ok(changed) => updated += usize::from(changed),
+            Err(err) => println!("ERROR {}: {err}", member.path.display()),
+        }
+    }
+
+    for group in &files.groups {
+        let remote = groups_by_id.get(group.id.as_ref()).or_else(|| {
+            group
+                .uuid
+                .as_ref()
+                .and_then(|uuid| groups_by_uuid.get(uuid.as_ref()))
+        });
+
+        if group.sync.is_skipped() {
+            if !quiet {
+                println!("SKIP   group ({}): sync is off", group.path.display());
+            }
+            continue;
+        }
+
+        let Some(remote) = remote else {
+            println!(
+                "WARN  {}: no matching group on PluralKit, skipping",
+                group.path.display()
+            );
+            continue;
+        };
+
+        let diff = GroupDiff::new(group, remote, &FieldOwners::default(), None, &mut |_| {
+            Resolution::Skip
+        });
+        let unchanged = unchanged_fields(&diff.snapshot, &diff.changes);
+        let values = group_values(
+            &conf.fields.group,
+            |field| {
+                conf.ownership.group.pulls(field)
+                    && (field == "uuid" || group.sync.includes(field))
+                    && (field != "privacy" || group.privacy.pulls_visibility())
+                    && !unchanged.contains(field)
+            },
+            remote,
+        );
+        match pull_note(
+            "group",
+            &remote.id.0,
+            &group.path,
+            &values,
+            conf.fields.group.case_insensitive,
+            quiet,
+            execute,
+        ) {
+            Ok(changed) => updated += usize::from(changed),
+            Err(err) => println!("ERROR {}: {err}", group.path.display()),
+        }
+    }
+
+    println!();
+    println!("{updated} note(s) to update");
+
+    if !execute {
+        println!("Dry run, pass `--execute` to apply changes");
+    }
+
+    Ok(())
+}
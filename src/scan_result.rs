@@ -1,7 +1,21 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
 use crate::markdown_objects::{MarkdownGroup, MarkdownMember};
 
+/// Group membership a scanner derived from where a member note is rather than its frontmatter
+#[derive(Debug)]
+pub(crate) struct GroupEdge {
+    pub(crate) member: PathBuf,
+    /// group id, group note name or pluralkit group name
+    pub(crate) group: String,
+}
+
 #[derive(Debug)]
 pub(crate) struct ScanResult {
     pub(crate) members: Vec<MarkdownMember>,
     pub(crate) groups: Vec<MarkdownGroup>,
+    pub(crate) edges: Vec<GroupEdge>,
+    /// Groups whose membership is owned by the scanner, member notes without an edge to one of
+    /// them are removed from it
+    pub(crate) edge_groups: BTreeSet<String>,
 }
@@ -1,8 +1,15 @@
+use std::{
+    collections::BTreeSet,
+    path::{Component, Path},
+};
+
+use walkdir::WalkDir;
+
 use crate::{
     config::{FieldConfig, GroupFieldConfig, MemberFieldConfig, PathScanConfig},
     markdown,
     markdown_objects::{MarkdownGroup, MarkdownMember},
-    scan_result::ScanResult,
+    scan_result::{GroupEdge, ScanResult},
 };
 
 fn scan_members(
@@ -75,13 +82,69 @@ fn scan_groups(
     Ok(groups)
 }
 
+/// Names of the folders between `root` and `path`
+fn folder_names(root: &Path, path: &Path) -> Vec<String> {
+    path.parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+        .map(|relative| {
+            relative
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(String::from(name.to_string_lossy())),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Every member note joins the groups named after the folders it's in
+fn folder_edges(
+    member_dir: &str,
+    members: &[MarkdownMember],
+) -> (Vec<GroupEdge>, BTreeSet<String>) {
+    let root = Path::new(member_dir);
+
+    let mut edges = Vec::new();
+    for member in members {
+        for group in folder_names(root, &member.path) {
+            edges.push(GroupEdge {
+                member: member.path.clone(),
+                group,
+            });
+        }
+    }
+
+    // empty folders still own their group, so moving the last note out of one empties it
+    let edge_groups = WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_dir())
+        .map(|entry| String::from(entry.file_name().to_string_lossy()))
+        .collect();
+
+    (edges, edge_groups)
+}
+
 pub(crate) fn run(cfg: &PathScanConfig, field_cfg: &FieldConfig) -> eyre::Result<ScanResult> {
+    let mut edges = Vec::new();
+    let mut edge_groups = BTreeSet::new();
+
     let members = if let Some(member_dir) = &cfg.member_dir {
-        scan_members(
-            &shellexpand::tilde(member_dir),
-            cfg.recursive,
-            &field_cfg.member,
-        )?
+        let member_dir = shellexpand::tilde(member_dir);
+        let members = scan_members(&member_dir, cfg.recursive, &field_cfg.member)?;
+
+        if cfg.folder_groups {
+            if !cfg.recursive {
+                println!(
+                    "WARN  `path_scanner.folder_groups` needs `recursive = true` to see subfolders"
+                );
+            }
+            (edges, edge_groups) = folder_edges(&member_dir, &members);
+        }
+
+        members
     } else {
         Vec::new()
     };
@@ -96,5 +159,10 @@ pub(crate) fn run(cfg: &PathScanConfig, field_cfg: &FieldConfig) -> eyre::Result
         Vec::new()
     };
 
-    Ok(ScanResult { members, groups })
+    Ok(ScanResult {
+        members,
+        groups,
+        edges,
+        edge_groups,
+    })
 }
@@ -1,11 +1,11 @@
 use frontmatter_gen::Frontmatter;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
 use crate::{
-    config::{FieldConfig, TagScanConfig},
+    config::{FieldConfig, MembershipMode, TagScanConfig},
     markdown,
     markdown_objects::{MarkdownGroup, MarkdownMember},
-    scan_result::ScanResult,
+    scan_result::{GroupEdge, ScanResult},
 };
 
 fn parse_tags(frontmatter: &Frontmatter) -> Option<HashSet<String>> {
@@ -33,9 +33,36 @@ fn parse_tags(frontmatter: &Frontmatter) -> Option<HashSet<String>> {
     Some(tag_set)
 }
 
-pub(crate) fn run(cfg: &TagScanConfig, field_cfg: &FieldConfig) -> eyre::Result<ScanResult> {
+/// Groups named by tags under `prefix`, every level of a nested tag is its own group
+///
+/// A leading `#` is ignored on both the prefix and the tags.
+fn tag_groups(prefix: &str, tags: &HashSet<String>) -> BTreeSet<String> {
+    let prefix = prefix.trim_start_matches('#').trim_end_matches('/');
+    tags.iter()
+        .filter_map(|tag| {
+            tag.trim_start_matches('#')
+                .strip_prefix(prefix)?
+                .strip_prefix('/')
+        })
+        .flat_map(|groups| groups.split('/'))
+        .filter(|group| !group.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Scan `cfg.root_dir` for tagged notes
+///
+/// Tag groups are only owned by the tags when `mode` removes memberships, member notes without
+/// the tag are then removed from them.
+pub(crate) fn run(
+    cfg: &TagScanConfig,
+    field_cfg: &FieldConfig,
+    mode: MembershipMode,
+) -> eyre::Result<ScanResult> {
     let mut members = Vec::new();
     let mut groups = Vec::new();
+    let mut edges = Vec::new();
+    let mut edge_groups = BTreeSet::new();
 
     for md_entry in markdown::walker(&cfg.root_dir, true) {
         let file_content = match std::fs::read_to_string(md_entry.path()) {
@@ -72,7 +99,20 @@ pub(crate) fn run(cfg: &TagScanConfig, field_cfg: &FieldConfig) -> eyre::Result<
                     println!("ERROR {}: {err}", md_entry.path().display());
                     continue;
                 }
-                Ok(member) => members.push(member),
+                Ok(member) => {
+                    if let Some(prefix) = &cfg.group_tag_prefix {
+                        for group in tag_groups(prefix, &tags) {
+                            if mode.removes() {
+                                edge_groups.insert(group.clone());
+                            }
+                            edges.push(GroupEdge {
+                                member: member.path.clone(),
+                                group,
+                            });
+                        }
+                    }
+                    members.push(member);
+                }
             }
         }
 
@@ -94,5 +134,21 @@ pub(crate) fn run(cfg: &TagScanConfig, field_cfg: &FieldConfig) -> eyre::Result<
         }
     }
 
-    Ok(ScanResult { members, groups })
+    // every group note can be tagged, so removing the last tag for one still empties it
+    if cfg.group_tag_prefix.is_some() && mode.removes() {
+        edge_groups.extend(
+            groups
+                .iter()
+                .filter(|group| group.sync.includes("members"))
+                .filter_map(|group| group.path.file_stem())
+                .map(|name| String::from(name.to_string_lossy())),
+        );
+    }
+
+    Ok(ScanResult {
+        members,
+        groups,
+        edges,
+        edge_groups,
+    })
 }
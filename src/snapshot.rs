@@ -0,0 +1,34 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// Field values after the last successful sync, formatted like the sync plan and keyed by field
+pub(crate) type FieldSnapshot = BTreeMap<String, String>;
+
+/// State of every member and group after the last successful sync, keyed by PluralKit uuid
+///
+/// Used as the merge base to tell edits in notes apart from edits on PluralKit.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct Snapshot {
+    #[serde(default)]
+    pub(crate) members: BTreeMap<String, FieldSnapshot>,
+    #[serde(default)]
+    pub(crate) groups: BTreeMap<String, FieldSnapshot>,
+}
+
+impl Snapshot {
+    /// Read the snapshot at `path`, nothing has been synced yet if it doesn't exist
+    pub(crate) fn load(path: &Path) -> eyre::Result<Self> {
+        if !std::fs::exists(path)? {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> eyre::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,682 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::eyre;
+use pkrs_fork::{
+    client::PkClient,
+    model::{Group, Member, PkId},
+};
+
+use crate::{
+    config::{Config, FieldKeys, FieldOwners, LimitConfig},
+    diff::{Conflict, FieldChange, GroupDiff, MemberDiff, Resolution},
+    frontmatter::{self, YamlValue},
+    get_files, limits, markdown_objects,
+    membership::{self, GroupTarget, MembershipPlan},
+    proxy_tags,
+    pull::{self, group_values, member_values, pull_field},
+    snapshot::{FieldSnapshot, Snapshot},
+};
+
+fn print_changes(kind: &str, id: &str, name: &str, changes: &[FieldChange], quiet: bool) {
+    if changes.is_empty() {
+        if !quiet {
+            println!("OK     {kind} {id} ({name})");
+        }
+        return;
+    }
+
+    println!("UPDATE {kind} {id} ({name})");
+    for change in changes {
+        println!("  {}: {} -> {}", change.field, change.remote, change.local);
+    }
+}
+
+fn print_conflicts(kind: &str, id: &str, name: &str, conflicts: &[Conflict]) {
+    if conflicts.is_empty() {
+        return;
+    }
+
+    println!("CONFLICT {kind} {id} ({name}) edited in the note and on PluralKit, skipping");
+    for conflict in conflicts {
+        println!(
+            "  {}: {} -> note {} / PluralKit {}",
+            conflict.field, conflict.base, conflict.local, conflict.remote
+        );
+    }
+}
+
+/// Check a value typed in for `field` like the same value read from a note, returns it the way
+/// it's sent to PluralKit
+fn validate_edit(limits: &LimitConfig, field: &'static str, text: &str) -> eyre::Result<String> {
+    if matches!(text, "" | "(none)") {
+        return match field {
+            "name" | "keep_proxy" | "tts" => Err(eyre!("`{field}` can't be cleared")),
+            _ => Ok(String::from(text)),
+        };
+    }
+
+    let keys = FieldKeys::from(field);
+    let (max, strategy) = match field {
+        "color" => {
+            return markdown_objects::parse_color(Some(&keys), Some(text))
+                .map(Option::unwrap_or_default);
+        }
+        "avatar_url" | "webhook_avatar_url" | "banner" | "icon" => {
+            return markdown_objects::parse_url(Some(&keys), Some(text))
+                .map(Option::unwrap_or_default);
+        }
+        "name" => (limits::NAME, limits.name),
+        "display_name" => (limits::DISPLAY_NAME, limits.display_name),
+        "description" => (limits::DESCRIPTION, limits.description),
+        _ => return Ok(String::from(text)),
+    };
+
+    let mut value = String::from(text);
+    match limits::enforce(field, &mut value, max, strategy) {
+        Some(violation) if violation.is_error() => Err(eyre!("{violation}")),
+        Some(violation) => {
+            println!("WARN  {violation}");
+            Ok(value)
+        }
+        None => Ok(value),
+    }
+}
+
+/// Ask which side of `conflict` to keep, stdin closing skips the conflict
+fn prompt_resolution(
+    kind: &str,
+    id: &str,
+    name: &str,
+    conflict: &Conflict,
+    limits: &LimitConfig,
+    writable: bool,
+) -> Resolution {
+    println!("CONFLICT {kind} {id} ({name}) {}", conflict.field);
+    println!("  base:      {}", conflict.base);
+    println!("  note:      {}", conflict.local);
+    println!("  PluralKit: {}", conflict.remote);
+    // any other value would be reverted by the next sync since the note keeps its value
+    if !writable {
+        println!("  the note can't be updated for this field, only its value can be kept");
+    }
+
+    let mut line = String::new();
+    loop {
+        if writable {
+            print!("Keep [n]ote, [p]luralkit, [e]dit or [s]kip? ");
+        } else {
+            print!("Keep [n]ote or [s]kip? ");
+        }
+        line.clear();
+        if io::stdout().flush().is_err() || io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            return Resolution::Skip;
+        }
+
+        match line.trim().to_ascii_lowercase().as_str() {
+            "n" | "note" => return Resolution::Local,
+            "s" | "skip" => return Resolution::Skip,
+            "p" | "pluralkit" if writable => return Resolution::Remote,
+            "e" | "edit" if writable => {
+                print!("New value, nothing or `(none)` clears it: ");
+                line.clear();
+                if io::stdout().flush().is_err()
+                    || io::stdin().read_line(&mut line).unwrap_or(0) == 0
+                {
+                    return Resolution::Skip;
+                }
+                match validate_edit(limits, conflict.field, line.trim()) {
+                    Ok(value) => return Resolution::Edit(value),
+                    Err(err) => println!("ERROR {err}"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `field` by `pull` name has to be written back to the note, see [`write_back`]
+fn needs_write_back(remote_changes: &[FieldChange], edited: &[&str], field: &str) -> bool {
+    remote_changes
+        .iter()
+        .map(|change| change.field)
+        .chain(edited.iter().copied())
+        .any(|changed| pull_field(changed) == field)
+}
+
+/// Keep fields only edited on PluralKit and write them and edited conflicts back to the note
+/// where possible
+///
+/// The snapshot keeps the old value until the next sync finds the note and PluralKit agree.
+fn write_back(
+    kind: &str,
+    id: &str,
+    path: &Path,
+    changes: &[FieldChange],
+    values: &[(FieldKeys, YamlValue)],
+    case_insensitive: bool,
+    execute: bool,
+) {
+    if !changes.is_empty() {
+        println!(
+            "KEEP   {kind} {id} ({}) edited on PluralKit",
+            path.display()
+        );
+        for change in changes {
+            println!("  {}: {} <- {}", change.field, change.local, change.remote);
+        }
+    }
+
+    if let Err(err) = pull::pull_note(kind, id, path, values, case_insensitive, true, execute) {
+        println!("ERROR {}: {err}", path.display());
+    }
+}
+
+/// Move the merge base to PluralKit's value for fields only edited there that can't be written
+/// to the note, see [`write_back`]
+///
+/// Otherwise they'd be kept on every sync and any later note edit would be a conflict, the note
+/// is the only source for these fields so its value is synced again next time.
+fn settle_unwritten(
+    path: &Path,
+    remote_changes: &[FieldChange],
+    written: &HashSet<String>,
+    snapshot: &mut FieldSnapshot,
+) {
+    for change in remote_changes {
+        if written.contains(pull_field(change.field)) {
+            continue;
+        }
+
+        println!(
+            "WARN  {}: {} can't be written to the note, the note's value is synced next time",
+            path.display(),
+            change.field
+        );
+        snapshot.insert(String::from(change.field), change.remote.clone());
+    }
+}
+
+fn print_create(kind: &str, path: &Path, changes: &[FieldChange]) {
+    println!("CREATE {kind} from {}", path.display());
+    for change in changes {
+        println!("  {}: {}", change.field, change.local);
+    }
+}
+
+pub(crate) async fn run(
+    pk: &PkClient,
+    conf: &Config,
+    quiet: bool,
+    execute: bool,
+    allow_proxy_collisions: bool,
+    interactive: bool,
+) -> eyre::Result<()> {
+    // a dry run doesn't save the snapshot or write notes, the answers would be thrown away
+    if interactive && !execute {
+        return Err(eyre!(
+            "`--interactive` only works together with `--execute`"
+        ));
+    }
+
+    let system = pk.get_system(&PkId("@me".into())).await?;
+    println!(
+        "Syncing System: {} ...",
+        system.name.as_deref().unwrap_or(&system.id.0)
+    );
+
+    let files = get_files(conf)?;
+
+    let snapshot_path = PathBuf::from(shellexpand::tilde(&conf.snapshot_file).as_ref());
+    let mut snapshot = Snapshot::load(&snapshot_path)?;
+
+    // tags owned by pluralkit are never written, so collisions in notes don't matter
+    let collisions = if conf.ownership.member.syncs("proxy_tags") {
+        proxy_tags::collisions(&files.members, &conf.fields.member.proxy_tag_placeholder)
+    } else {
+        Vec::new()
+    };
+    for collision in &collisions {
+        if allow_proxy_collisions {
+            println!("WARN  {collision}");
+        } else {
+            println!("ERROR {collision}");
+        }
+    }
+    if !collisions.is_empty() && !allow_proxy_collisions {
+        return Err(eyre!(
+            "{} proxy tag collision(s), pass `--allow-proxy-collisions` to sync anyway",
+            collisions.len()
+        ));
+    }
+
+    let remote_members = pk.get_system_members(&system.id).await?;
+    let remote_members: HashMap<&str, &Member> = remote_members
+        .iter()
+        .map(|member| (member.id.0.as_str(), member))
+        .collect();
+
+    let remote_group_list = pk.get_system_groups(&system.id).await?;
+    let remote_groups: HashMap<&str, &Group> = remote_group_list
+        .iter()
+        .map(|group| (group.id.0.as_str(), group))
+        .collect();
+
+    let mut conflicts = 0;
+    let mut member_creates = Vec::new();
+    let mut member_diffs = Vec::new();
+    for member in &files.members {
+        if member.sync.is_skipped() {
+            if !quiet {
+                println!("SKIP   member ({}): sync is off", member.path.display());
+            }
+            continue;
+        }
+
+        let Some(id) = &member.id else {
+            if !member.sync.includes("name") {
+                println!(
+                    "ERROR {}: can't create a member without syncing its name, skipping",
+                    member.path.display()
+                );
+                continue;
+            }
+
+            // new members have nothing on pluralkit to clobber yet
+            let diff = MemberDiff::new(
+                member,
+                &Member::default(),
+                &FieldOwners::default(),
+                &conf.fields.member.proxy_tag_placeholder,
+                None,
+                &mut |_| Resolution::Skip,
+            );
+            print_create("member", &member.path, &diff.changes);
+            let groups = membership::new_member_groups(member, &files, &remote_group_list);
+            if !groups.is_empty() {
+                let names: Vec<String> = groups.iter().map(ToString::to_string).collect();
+                println!("  groups: {}", names.join(", "));
+            }
+            member_creates.push(diff);
+            continue;
+        };
+
+        let Some(remote) = remote_members.get(id.as_ref()) else {
+            println!(
+                "ERROR {}: member `{}` not found on PluralKit",
+                member.path.display(),
+                id.as_ref()
+            );
+            continue;
+        };
+
+        if let Some(uuid) = &member.uuid
+            && uuid.as_ref() != remote.uuid.to_string()
+        {
+            println!(
+                "ERROR {}: uuid `{}` doesn't match PluralKit uuid `{}` for member `{}`, skipping",
+                member.path.display(),
+                uuid.as_ref(),
+                remote.uuid,
+                id.as_ref()
+            );
+            continue;
+        }
+
+        let uuid = remote.uuid.to_string();
+        let mut resolve = |conflict: &Conflict| {
+            if interactive {
+                prompt_resolution(
+                    "member",
+                    id.as_ref(),
+                    &remote.name,
+                    conflict,
+                    &conf.limits,
+                    pull::member_field_writable(
+                        &conf.fields.member,
+                        &conf.name,
+                        member,
+                        conflict.field,
+                    ),
+                )
+            } else {
+                Resolution::Skip
+            }
+        };
+        let mut diff = MemberDiff::new(
+            member,
+            remote,
+            &conf.ownership.member,
+            &conf.fields.member.proxy_tag_placeholder,
+            snapshot.members.get(&uuid),
+            &mut resolve,
+        );
+        print_changes("member", id.as_ref(), &remote.name, &diff.changes, quiet);
+        print_conflicts("member", id.as_ref(), &remote.name, &diff.conflicts);
+        conflicts += diff.conflicts.len();
+
+        let written = RefCell::new(HashSet::new());
+        let values = member_values(
+            &conf.fields.member,
+            &conf.name,
+            |field| {
+                let write = needs_write_back(&diff.remote_changes, &diff.edited, field)
+                    && (field != "privacy" || member.privacy.pulls_visibility());
+                if write {
+                    written.borrow_mut().insert(String::from(field));
+                }
+                write
+            },
+            &diff.patched,
+        );
+        if !diff.remote_changes.is_empty() || !diff.edited.is_empty() {
+            write_back(
+                "member",
+                id.as_ref(),
+                &member.path,
+                &diff.remote_changes,
+                &values,
+                conf.fields.member.case_insensitive,
+                execute,
+            );
+        }
+        settle_unwritten(
+            &member.path,
+            &diff.remote_changes,
+            &written.into_inner(),
+            &mut diff.snapshot,
+        );
+
+        if diff.changes.is_empty() {
+            snapshot.members.insert(uuid, diff.snapshot);
+        } else {
+            member_diffs.push(diff);
+        }
+    }
+
+    let mut group_diffs = Vec::new();
+    for group in &files.groups {
+        if group.sync.is_skipped() {
+            if !quiet {
+                println!("SKIP   group ({}): sync is off", group.path.display());
+            }
+            continue;
+        }
+
+        let Some(remote) = remote_groups.get(group.id.as_ref()) else {
+            println!(
+                "ERROR {}: group `{}` not found on PluralKit",
+                group.path.display(),
+                group.id.as_ref()
+            );
+            continue;
+        };
+
+        if let Some(uuid) = &group.uuid
+            && uuid.as_ref() != remote.uuid.to_string()
+        {
+            println!(
+                "ERROR {}: uuid `{}` doesn't match PluralKit uuid `{}` for group `{}`, skipping",
+                group.path.display(),
+                uuid.as_ref(),
+                remote.uuid,
+                group.id.as_ref()
+            );
+            continue;
+        }
+
+        let uuid = remote.uuid.to_string();
+        let mut resolve = |conflict: &Conflict| {
+            if interactive {
+                prompt_resolution(
+                    "group",
+                    group.id.as_ref(),
+                    &remote.name,
+                    conflict,
+                    &conf.limits,
+                    pull::group_field_writable(&conf.fields.group, group, conflict.field),
+                )
+            } else {
+                Resolution::Skip
+            }
+        };
+        let mut diff = GroupDiff::new(
+            group,
+            remote,
+            &conf.ownership.group,
+            snapshot.groups.get(&uuid),
+            &mut resolve,
+        );
+        print_changes(
+            "group",
+            group.id.as_ref(),
+            &remote.name,
+            &diff.changes,
+            quiet,
+        );
+        print_conflicts("group", group.id.as_ref(), &remote.name, &diff.conflicts);
+        conflicts += diff.conflicts.len();
+
+        let written = RefCell::new(HashSet::new());
+        let values = group_values(
+            &conf.fields.group,
+            |field| {
+                let write = needs_write_back(&diff.remote_changes, &diff.edited, field)
+                    && (field != "privacy" || group.privacy.pulls_visibility());
+                if write {
+                    written.borrow_mut().insert(String::from(field));
+                }
+                write
+            },
+            &diff.patched,
+        );
+        if !diff.remote_changes.is_empty() || !diff.edited.is_empty() {
+            write_back(
+                "group",
+                group.id.as_ref(),
+                &group.path,
+                &diff.remote_changes,
+                &values,
+                conf.fields.group.case_insensitive,
+                execute,
+            );
+        }
+        settle_unwritten(
+            &group.path,
+            &diff.remote_changes,
+            &written.into_inner(),
+            &mut diff.snapshot,
+        );
+
+        if diff.changes.is_empty() {
+            snapshot.groups.insert(uuid, diff.snapshot);
+        } else {
+            group_diffs.push(diff);
+        }
+    }
+
+    // memberships owned by pluralkit are left alone, new members still get their groups
+    let syncs_groups = conf.ownership.member.syncs("groups");
+    let syncs_members = conf.ownership.group.syncs("members");
+
+    let mut membership_plan = MembershipPlan::default();
+    if (syncs_groups
+        && (files.members.iter().any(|member| member.groups.is_some())
+            || !files.edges.is_empty()
+            || !files.edge_groups.is_empty()))
+        || (syncs_members && files.groups.iter().any(|group| group.members.is_some()))
+    {
+        let remote = membership::remote_memberships(pk, &remote_group_list).await?;
+        if syncs_groups {
+            membership_plan.add_member_declarations(
+                &files.members,
+                &files.groups,
+                &remote,
+                conf.membership.mode,
+            );
+            membership_plan.add_edges(&files, &remote_group_list, &remote);
+        }
+        if syncs_members {
+            membership_plan.add_group_declarations(
+                &files.groups,
+                &files.members,
+                &remote,
+                conf.membership.mode,
+            );
+        }
+    }
+    membership_plan.print(&remote_members, &remote_groups);
+
+    println!();
+    println!(
+        "{} member(s) to create, {} member(s) and {} group(s) to update",
+        member_creates.len(),
+        member_diffs.len(),
+        group_diffs.len(),
+    );
+    println!(
+        "{} group(s) with membership changes",
+        membership_plan.changed_groups()
+    );
+    if conflicts > 0 {
+        println!(
+            "{conflicts} conflicting field(s) left alone, pass `--execute --interactive` to resolve them"
+        );
+    }
+
+    if !execute {
+        println!("Dry run, pass `--execute` to apply changes");
+        return Ok(());
+    }
+
+    let member_cfg = &conf.fields.member;
+    for diff in member_creates {
+        let created = match pk.create_member(&diff.patched).await {
+            Ok(created) => created,
+            Err(err) => {
+                println!(
+                    "ERROR {}: failed to create member: {err}",
+                    diff.local.path.display()
+                );
+                continue;
+            }
+        };
+        println!(
+            "OK     created member {} from {}",
+            created.id.0,
+            diff.local.path.display()
+        );
+        snapshot
+            .members
+            .insert(created.uuid.to_string(), diff.snapshot);
+
+        let written = frontmatter::edit_file(&diff.local.path, |fm| {
+            let id_key = fm.resolve_key(&member_cfg.id, member_cfg.case_insensitive);
+            fm.set_field(
+                &id_key,
+                &YamlValue::String(created.id.0.clone()),
+                member_cfg.case_insensitive,
+            )?;
+            if let Some(uuid_keys) = &member_cfg.uuid {
+                let uuid_key = fm.resolve_key(uuid_keys, member_cfg.case_insensitive);
+                fm.set_field(
+                    &uuid_key,
+                    &YamlValue::String(created.uuid.to_string()),
+                    member_cfg.case_insensitive,
+                )?;
+            }
+            Ok(())
+        });
+
+        if let Err(err) = written {
+            println!(
+                "ERROR {}: failed to write id `{}` back to note: {err}",
+                diff.local.path.display(),
+                created.id.0
+            );
+        }
+
+        for target in membership::new_member_groups(diff.local, &files, &remote_group_list) {
+            let GroupTarget::Existing(group_id) = &target else {
+                // created along with the other new groups once all members exist
+                membership_plan.add_to(&target, &created.id.0);
+                continue;
+            };
+
+            let group_id = PkId(group_id.clone());
+            if let Err(err) = pk
+                .add_group_members(&group_id, std::slice::from_ref(&created.id))
+                .await
+            {
+                println!(
+                    "ERROR {}: failed to add member to group `{}`: {err}",
+                    diff.local.path.display(),
+                    group_id.0
+                );
+            }
+        }
+    }
+
+    for diff in member_diffs {
+        match pk.update_member(&diff.patched).await {
+            Ok(updated) => {
+                println!("OK     updated member {}", updated.id.0);
+                snapshot
+                    .members
+                    .insert(diff.patched.uuid.to_string(), diff.snapshot);
+            }
+            Err(err) => println!(
+                "ERROR {}: failed to update member: {err}",
+                diff.local.path.display()
+            ),
+        }
+    }
+
+    for diff in group_diffs {
+        match pk.update_group(&diff.patched).await {
+            Ok(_) => {
+                println!("OK     updated group {}", diff.local.id.as_ref());
+                snapshot
+                    .groups
+                    .insert(diff.patched.uuid.to_string(), diff.snapshot);
+            }
+            Err(err) => println!(
+                "ERROR {}: failed to update group: {err}",
+                diff.local.path.display()
+            ),
+        }
+    }
+
+    membership_plan.apply(pk).await;
+
+    snapshot.save(&snapshot_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edited_values_are_validated_like_notes() {
+        let limits = LimitConfig::default();
+        assert_eq!(
+            validate_edit(&limits, "color", "#FF00AA").unwrap(),
+            "ff00aa"
+        );
+        assert!(validate_edit(&limits, "color", "red").is_err());
+        assert!(validate_edit(&limits, "avatar_url", "example.com/a.png").is_err());
+        assert!(validate_edit(&limits, "name", &"a".repeat(limits::NAME + 1)).is_err());
+        assert_eq!(validate_edit(&limits, "color", "(none)").unwrap(), "(none)");
+        assert!(validate_edit(&limits, "name", "").is_err());
+        assert!(validate_edit(&limits, "name", "(none)").is_err());
+        assert!(validate_edit(&limits, "tts", "").is_err());
+        assert_eq!(
+            validate_edit(&limits, "pronouns", "she/her").unwrap(),
+            "she/her"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::BTreeSet, fmt};
+
+use eyre::eyre;
+
+use crate::{
+    config::FieldKeys,
+    fields::{self, FieldReader},
+};
+
+/// Fields of a member note that can be synced on their own, named like the sync plan fields
+pub(crate) const MEMBER_FIELDS: &[&str] = &[
+    "name",
+    "display_name",
+    "description",
+    "pronouns",
+    "proxy_tags",
+    "avatar_url",
+    "webhook_avatar_url",
+    "banner",
+    "color",
+    "birthday",
+    "keep_proxy",
+    "tts",
+    "autoproxy_enabled",
+    "privacy",
+    "groups",
+];
+
+/// Fields of a group note that can be synced on their own, named like the sync plan fields
+pub(crate) const GROUP_FIELDS: &[&str] = &[
+    "name",
+    "display_name",
+    "description",
+    "icon",
+    "banner",
+    "color",
+    "privacy",
+    "members",
+];
+
+/// Which fields of a note are synced, set per note with e.g. `pk_sync: false`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) enum SyncDirective {
+    #[default]
+    All,
+    /// the note is left out of sync entirely
+    Skip,
+    /// only the listed fields are synced
+    Only(BTreeSet<&'static str>),
+}
+
+impl SyncDirective {
+    /// Read the sync field, either a bool or a list of fields, see [`MEMBER_FIELDS`]
+    pub(crate) fn parse(
+        fields: &FieldReader,
+        field: Option<&FieldKeys>,
+        known: &'static [&'static str],
+    ) -> eyre::Result<Self> {
+        let Some((field, value)) = fields.value(field) else {
+            return Ok(Self::All);
+        };
+
+        if let Some(sync) = fields::to_bool(value) {
+            return Ok(if sync { Self::All } else { Self::Skip });
+        }
+
+        let Some(names) = fields.coerce_list(&field, value) else {
+            return Err(eyre!(
+                "`{field}` must be yes/no or a list of fields, got `{value:?}`"
+            ));
+        };
+
+        let mut only = BTreeSet::new();
+        for name in names {
+            let Some(known_name) = known.iter().find(|known| known.eq_ignore_ascii_case(&name))
+            else {
+                return Err(eyre!(
+                    "unknown field `{name}` in `{field}`, valid fields are {}",
+                    known.join(", ")
+                ));
+            };
+            only.insert(*known_name);
+        }
+
+        Ok(Self::Only(only))
+    }
+
+    /// Whether `field` is synced
+    pub(crate) fn includes(&self, field: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Skip => false,
+            Self::Only(fields) => fields.contains(field),
+        }
+    }
+
+    pub(crate) fn is_skipped(&self) -> bool {
+        matches!(self, Self::Skip)
+    }
+}
+
+impl fmt::Display for SyncDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Skip => write!(f, "off"),
+            Self::Only(fields) => {
+                write!(
+                    f,
+                    "{}",
+                    fields.iter().copied().collect::<Vec<_>>().join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use frontmatter_gen::{Frontmatter, Value};
+
+    use super::*;
+
+    fn parse(value: Value) -> eyre::Result<SyncDirective> {
+        let mut frontmatter = Frontmatter::new();
+        frontmatter.insert(String::from("pk_sync"), value);
+        let fields = FieldReader::new(Path::new("Alex.md"), &frontmatter, false);
+        SyncDirective::parse(&fields, Some(&FieldKeys::from("pk_sync")), MEMBER_FIELDS)
+    }
+
+    #[test]
+    fn missing_field_syncs_everything() {
+        let frontmatter = Frontmatter::new();
+        let fields = FieldReader::new(Path::new("Alex.md"), &frontmatter, false);
+        let sync = SyncDirective::parse(&fields, Some(&FieldKeys::from("pk_sync")), MEMBER_FIELDS);
+        assert_eq!(sync.unwrap(), SyncDirective::All);
+    }
+
+    #[test]
+    fn bools_toggle_the_whole_note() {
+        assert_eq!(parse(Value::Boolean(true)).unwrap(), SyncDirective::All);
+        assert_eq!(parse(Value::Boolean(false)).unwrap(), SyncDirective::Skip);
+        assert_eq!(
+            parse(Value::String(String::from("off"))).unwrap(),
+            SyncDirective::Skip
+        );
+    }
+
+    #[test]
+    fn lists_sync_only_those_fields() {
+        let sync = parse(Value::Array(vec![
+            Value::String(String::from("Name")),
+            Value::String(String::from("pronouns")),
+        ]))
+        .unwrap();
+        assert_eq!(
+            sync,
+            SyncDirective::Only(BTreeSet::from(["name", "pronouns"]))
+        );
+        assert!(sync.includes("name"));
+        assert!(!sync.includes("description"));
+        assert_eq!(sync.to_string(), "name, pronouns");
+
+        let sync = parse(Value::String(String::from("name, color"))).unwrap();
+        assert_eq!(sync, SyncDirective::Only(BTreeSet::from(["color", "name"])));
+    }
+
+    #[test]
+    fn unknown_fields_error() {
+        assert!(parse(Value::String(String::from("name, nickname"))).is_err());
+        assert!(parse(Value::String(String::from("maybe"))).is_err());
+    }
+}